@@ -0,0 +1,44 @@
+/*
+ * C interface to the grepr search engine.
+ *
+ * Build the library with the `ffi` feature, for example:
+ *
+ *     cargo rustc --release --no-default-features --features ffi --crate-type cdylib
+ *
+ * All strings are NUL-terminated UTF-8. Every handle returned by a
+ * `_new` or `grepr_search` call must be released with its `_free`.
+ */
+#ifndef GREPR_H
+#define GREPR_H
+
+#include <stdbool.h>
+#include <stddef.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct GreprOptions GreprOptions;
+typedef struct GreprResults GreprResults;
+
+/* Returns NULL if query is NULL or not valid UTF-8. */
+GreprOptions *grepr_options_new(const char *query);
+void grepr_options_set_ignore_case(GreprOptions *options, bool value);
+void grepr_options_set_invert_match(GreprOptions *options, bool value);
+void grepr_options_set_word(GreprOptions *options, bool value);
+void grepr_options_set_line(GreprOptions *options, bool value);
+void grepr_options_free(GreprOptions *options);
+
+/* Returns NULL if an argument is NULL, contents is not UTF-8 or the search fails. */
+GreprResults *grepr_search(const GreprOptions *options, const char *contents);
+size_t grepr_results_len(const GreprResults *results);
+size_t grepr_results_line_number(const GreprResults *results, size_t index);
+/* The returned string is owned by results. */
+const char *grepr_results_line(const GreprResults *results, size_t index);
+void grepr_results_free(GreprResults *results);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GREPR_H */
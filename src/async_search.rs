@@ -0,0 +1,140 @@
+//! Non-blocking search built on tokio's async IO.
+//!
+//! Only available with the `async` feature. Lines are pulled from an
+//! async reader so the caller's executor is never blocked on file IO,
+//! and each one is matched by the same code as `Search::find`.
+use std::error::Error;
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+use crate::messages::Message;
+use crate::{binary_patterns, decode, CommandArgs, CompiledQuery, Devices, LineSearch, OwnedMatch, Stats};
+
+
+/// Searches the file at `args.path` without blocking the executor.
+///
+/// # Returns
+/// Returns every matching line, or an error if the path is a FIFO,
+/// socket or device and `args.devices` is `Skip`.
+///
+pub async fn search_path(args: &CommandArgs) -> Result<Vec<OwnedMatch>, Box<dyn Error + Send + Sync>> {
+    let lang = args.lang();
+    let file_type = tokio::fs::metadata(&args.path).await.map_err(|e| lang.io_error(e))?.file_type();
+    if args.devices == Devices::Skip && !file_type.is_file() && !file_type.is_dir() {
+        return Err(format!("{}: {}", args.path.display(), lang.text(Message::SkippedSpecialFile)).into());
+    }
+    let file = File::open(&args.path).await.map_err(|e| lang.io_error(e))?;
+    search_reader(BufReader::new(file), args).await
+}
+
+/// Searches the lines produced by an async reader.
+///
+/// Each line is decoded as `read` would decode the whole file, so
+/// `binary` and `strip_ansi` apply, and the search stops after
+/// `max_total` results.
+///
+/// # Returns
+/// Returns every matching line, or an error if the query is invalid or
+/// a line is not valid UTF-8.
+///
+pub async fn search_reader<R>(reader: R, args: &CommandArgs) -> Result<Vec<OwnedMatch>, Box<dyn Error + Send + Sync>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let decoded;
+    let args = match args.binary {
+        true => {
+            decoded = binary_patterns(args)?;
+            &decoded
+        }
+        false => args,
+    };
+    let query = CompiledQuery::new(args).map_err(|e| e.to_string())?;
+    let mut lines = LineSearch::new(&query);
+    let mut stats = Stats::default();
+    let mut results = Vec::new();
+    let mut segments = reader.split(b'\n');
+    let mut number = 0;
+    while let Some(mut bytes) = segments.next_segment().await? {
+        if args.max_total.is_some_and(|max| results.len() >= max) {
+            break;
+        }
+        if args.line_range.and_then(|range| range.end).is_some_and(|end| number > end) {
+            break;
+        }
+        if args.line_range.is_some_and(|range| !range.contains(number)) {
+            number += 1;
+            continue;
+        }
+        if bytes.ends_with(b"\r") {
+            bytes.pop();
+        }
+        let line = decode(args, bytes).map_err(|e| format!("line {number}: {e}"))?;
+        if let Some(span) = lines.select(&query, &line, &mut stats) {
+            results.push(OwnedMatch { number, line, span });
+        }
+        number += 1;
+    }
+
+    Ok(results)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::Span;
+
+    #[test]
+    fn test_search_reader_partial() {
+        let args = CommandArgs::new("ano".to_string(), PathBuf::new(), false, false, false, false);
+        let contents: &[u8] = b"this is a test.\nthis is another test!";
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let results = runtime.block_on(search_reader(contents, &args)).unwrap();
+
+        assert_eq!(results, vec![OwnedMatch { number: 1, line: "this is another test!".to_string(), span: Some(Span { start: 8, end: 11 }) }])
+    }
+
+    #[test]
+    fn test_search_reader_matches_search_str() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_strip_ansi(true)
+            .with_max_line_length(Some(12), false)
+            .with_max_total(Some(2));
+        let contents = "a \u{1b}[31mte\u{1b}[0mst\r\na test that is too long\nno\ntest 2\ntest 3\n";
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let results = runtime.block_on(search_reader(contents.as_bytes(), &args)).unwrap();
+        let expected: Vec<(usize, String)> = crate::search_str(&crate::strip_ansi(contents), &args)
+            .unwrap()
+            .into_iter()
+            .map(|(number, line)| (number, line.to_string()))
+            .collect();
+
+        assert_eq!(results.into_iter().map(|m| (m.number, m.line)).collect::<Vec<_>>(), expected);
+        assert_eq!(expected.iter().map(|m| m.0).collect::<Vec<_>>(), vec![0, 3])
+    }
+
+    #[test]
+    fn test_search_reader_binary() {
+        let args = CommandArgs::new(r"\xde\xad".to_string(), PathBuf::new(), false, false, false, false)
+            .with_binary(true);
+        let contents: &[u8] = b"text\n\x00\xde\xad\xbe\xef\n";
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let results = runtime.block_on(search_reader(contents, &args)).unwrap();
+
+        assert_eq!(results.iter().map(|m| m.number).collect::<Vec<_>>(), vec![1])
+    }
+
+    #[test]
+    fn test_search_path_missing_file() {
+        let args = CommandArgs::new("ano".to_string(), PathBuf::from("does/not/exist.txt"), false, false, false, false);
+
+        let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+        let results = runtime.block_on(search_path(&args));
+
+        assert!(results.is_err())
+    }
+}
@@ -0,0 +1,220 @@
+//! Caching the results of searching a file on disk.
+//!
+//! Only used with `--cache`. Entries live in `$XDG_CACHE_HOME/grepr`, or
+//! `~/.cache/grepr`, one file per search. An entry is named after a hash
+//! of the file's path, the search options and the grepr version, and
+//! starts with a hash of the contents it was made from, so an entry for a
+//! changed file or query is never used. Only the `MAX_ENTRIES` most
+//! recently written entries are kept. The cache is best effort: an entry
+//! that can't be read or written is treated as missing.
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::SystemTime;
+use crate::{CommandArgs, Match, Search, Span, Stats};
+
+
+// The number of entries kept before the oldest are removed.
+const MAX_ENTRIES: usize = 256;
+
+
+// Fills `search` with the cached results for `args`, if there are any.
+// Returns true if it did.
+pub(crate) fn load(args: &CommandArgs, search: &mut Search) -> bool {
+    dir().is_some_and(|dir| load_from(&dir, args, search))
+}
+
+// Saves the results in `search` for the next search with `args`.
+pub(crate) fn store(args: &CommandArgs, search: &Search) {
+    if let Some(dir) = dir() {
+        store_in(&dir, args, search);
+    }
+}
+
+// Returns the directory holding the cache entries.
+fn dir() -> Option<PathBuf> {
+    let cache_dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache_dir.join("grepr"))
+}
+
+// Returns the path of the entry for searching `args.path` with `args`,
+// or `None` if it isn't a regular file.
+fn entry(dir: &Path, args: &CommandArgs) -> Option<PathBuf> {
+    fs::metadata(&args.path).ok().filter(|metadata| metadata.is_file())?;
+    let path = fs::canonicalize(&args.path).ok()?;
+    // The `Debug` output covers every option, and only changes between
+    // versions, which are part of the key.
+    let key = format!("{}\0{}\0{args:?}", env!("CARGO_PKG_VERSION"), path.display());
+    Some(dir.join(format!("{:016x}", fnv1a(key.as_bytes()))))
+}
+
+// Hashes `bytes` with 64-bit FNV-1a, which unlike the standard library's
+// hasher gives the same result in every build.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn load_from(dir: &Path, args: &CommandArgs, search: &mut Search) -> bool {
+    let Some(contents) = entry(dir, args).and_then(|path| fs::read_to_string(path).ok()) else {
+        return false;
+    };
+    match parse(&contents, search.contents) {
+        Some((matches, stats)) => {
+            search.results = matches.iter().map(|m| (m.number, m.line)).collect();
+            search.matches = matches;
+            search.stats.set(stats);
+            true
+        }
+        None => false,
+    }
+}
+
+fn store_in(dir: &Path, args: &CommandArgs, search: &Search) {
+    let Some(path) = entry(dir, args) else {
+        return;
+    };
+    let stats = search.get_stats();
+    let mut contents = format!(
+        "{:016x} {}\n{} {} {} {} {}\n",
+        fnv1a(search.contents.as_bytes()),
+        search.contents.len(),
+        stats.lines_searched, stats.lines_matched, stats.lines_skipped, stats.lines_truncated, stats.bytes_searched,
+    );
+    for m in &search.matches {
+        let start = search.byte_range(m).start;
+        contents.push_str(&format!("{} {start} {}", m.number, m.line.len()));
+        if let Some(span) = m.span {
+            contents.push_str(&format!(" {} {}", span.start, span.end));
+        }
+        contents.push('\n');
+    }
+    // Written under a temporary name first, so a concurrent search never
+    // reads half an entry.
+    let partial = path.with_extension(process::id().to_string());
+    let written = fs::create_dir_all(dir).and_then(|_| fs::write(&partial, contents));
+    if written.and_then(|_| fs::rename(&partial, &path)).is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    evict(dir, MAX_ENTRIES);
+}
+
+// Removes the oldest entries in `dir` until at most `max` are left.
+fn evict(dir: &Path, max: usize) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries: Vec<(SystemTime, PathBuf)> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    if entries.len() <= max {
+        return;
+    }
+    entries.sort();
+    for (_, path) in &entries[..entries.len() - max] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// Parses an entry written by `store_in` back into matches on `text`.
+// Returns `None` if the entry was made from other contents, or doesn't
+// fit `text`.
+fn parse<'a>(contents: &str, text: &'a str) -> Option<(Vec<Match<'a>>, Stats)> {
+    let mut lines = contents.lines();
+    let (hash, len) = lines.next()?.split_once(' ')?;
+    if u64::from_str_radix(hash, 16).ok()? != fnv1a(text.as_bytes()) || len.parse::<usize>().ok()? != text.len() {
+        return None;
+    }
+    let counts = numbers(lines.next()?)?;
+    let [lines_searched, lines_matched, lines_skipped, lines_truncated, bytes_searched] = counts[..] else {
+        return None;
+    };
+    let stats = Stats { lines_searched, lines_matched, lines_skipped, lines_truncated, bytes_searched };
+    let mut matches = Vec::new();
+    for line in lines {
+        let (number, start, len, span) = match numbers(line)?[..] {
+            [number, start, len] => (number, start, len, None),
+            [number, start, len, span_start, span_end] => (number, start, len, Some(Span { start: span_start, end: span_end })),
+            _ => return None,
+        };
+        let line = text.get(start..start.checked_add(len)?)?;
+        if span.is_some_and(|span| span.start > span.end || line.get(span.start..span.end).is_none()) {
+            return None;
+        }
+        matches.push(Match { number, line, span });
+    }
+    Some((matches, stats))
+}
+
+// Parses a line of space separated numbers.
+fn numbers(line: &str) -> Option<Vec<usize>> {
+    line.split(' ').map(|n| n.parse().ok()).collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IsSearch;
+
+    #[test]
+    fn test_cache_round_trip_and_invalidation() {
+        let dir = env::temp_dir().join(format!("grepr-cache-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("notes.txt");
+        fs::write(&path, "one test\ntwo\nthree test\n").unwrap();
+        let args = CommandArgs::new("test".to_string(), path.clone(), false, false, false, false);
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut search = Search::new(&contents);
+        search.find(&args).unwrap();
+        store_in(&dir, &args, &search);
+
+        let mut cached = Search::new(&contents);
+        let loaded = load_from(&dir, &args, &mut cached);
+        let other_query = args.clone().with_case_sensitive(true);
+        let missed_option = load_from(&dir, &other_query, &mut Search::new(&contents));
+        // Same size, so only the contents hash tells the entry is stale.
+        let changed = "one text\ntwo\nthree test\n";
+        let missed_change = load_from(&dir, &args, &mut Search::new(changed));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(loaded);
+        assert_eq!(cached.get_matches(), search.get_matches());
+        assert_eq!(cached.get_stats(), search.get_stats());
+        assert!(!missed_option);
+        assert!(!missed_change)
+    }
+
+    #[test]
+    fn test_parse_rejects_entry_for_other_contents() {
+        let header = format!("{:016x} 4\n", fnv1a(b"test"));
+
+        assert!(parse(&format!("{header}1 1 0 0 4\n0 0 4 0 4\n"), "test").is_some());
+        assert!(parse(&format!("{header}1 1 0 0 4\n0 0 4 0 4\n"), "tent").is_none());
+        assert!(parse(&format!("{header}1 1 0 0 4\n0 2 4 0 4\n"), "test").is_none());
+        assert!(parse(&format!("{header}1 1 0 0 4\n0 0 4 3 9\n"), "test").is_none())
+    }
+
+    #[test]
+    fn test_evict_keeps_newest_entries() {
+        let dir = env::temp_dir().join(format!("grepr-evict-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in ["a", "b", "c"] {
+            fs::write(dir.join(name), name).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        evict(&dir, 2);
+        let mut kept: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        kept.sort();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(kept, vec!["b", "c"])
+    }
+}
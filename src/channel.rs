@@ -0,0 +1,130 @@
+//! Streaming search results over a channel.
+//!
+//! The file is read and searched on a background thread, and each result
+//! is sent as soon as it is found. This suits GUI and language server
+//! style hosts that render results as they arrive instead of waiting for
+//! the whole search to finish.
+use std::error::Error;
+use std::ops::ControlFlow;
+use std::sync::mpsc::{self, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use crate::{binary_patterns, CommandArgs, Match, RunArgs, Search, SearchStats, Span};
+
+
+/// A `Match` that owns its line, so it can be sent between threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedMatch {
+    /// Line number within the contents.
+    pub number: usize,
+    /// The full text of the line.
+    pub line: String,
+    /// Location of the first match in `line`.
+    pub span: Option<Span>,
+}
+
+/// The thread running a `search_channel` search.
+///
+/// Joining it gives the search's `SearchStats`, or an error if the file
+/// could not be read.
+///
+pub type SearchHandle = JoinHandle<Result<SearchStats, Box<dyn Error + Send + Sync>>>;
+
+/// Searches the file at `args.path` on a background thread.
+///
+/// Results are sent over the returned channel in order, the same ones
+/// `search_str` would give, so `binary` patterns are decoded and no more
+/// than `max_total` are sent. Dropping the receiver stops the search at
+/// the next result.
+///
+/// # Returns
+/// Returns the receiving end of the channel and a handle to the thread.
+///
+/// # Example
+/// ```no_run
+/// # use crate::grepr::{search_channel, CommandArgs};
+/// # use std::path::PathBuf;
+/// let args = CommandArgs::new("test".to_string(), PathBuf::from("notes.txt"), false, false, false, false);
+/// let (results, handle) = search_channel(args);
+///
+/// for m in results {
+///     println!("{}: {}", m.number, m.line);
+/// }
+/// let stats = handle.join().unwrap().unwrap();
+/// ```
+///
+pub fn search_channel(args: CommandArgs) -> (Receiver<OwnedMatch>, SearchHandle) {
+    let (sender, receiver) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let start = Instant::now();
+        let args = match args.binary {
+            true => binary_patterns(&args)?,
+            false => args,
+        };
+        let contents = args.read().map_err(|e| e.to_string())?;
+        let search = Search::new(&contents);
+        let mut count = 0;
+        if args.max_total != Some(0) {
+            let sent = search.find_with(&args, |m| {
+                if sender.send(OwnedMatch::from(m)).is_err() {
+                    return ControlFlow::Break(());
+                }
+                count += 1;
+                match args.max_total.is_some_and(|max| count >= max) {
+                    true => ControlFlow::Break(()),
+                    false => ControlFlow::Continue(()),
+                }
+            });
+            let _ = sent.map_err(|e| e.to_string())?;
+        }
+        Ok(SearchStats { files_searched: 1, files_skipped: 0, lines: search.get_stats(), elapsed: start.elapsed() })
+    });
+    (receiver, handle)
+}
+
+impl From<Match<'_>> for OwnedMatch {
+    fn from(m: Match<'_>) -> OwnedMatch {
+        OwnedMatch { number: m.number, line: m.line.to_string(), span: m.span }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_search_channel_streams_results() {
+        let path = std::env::temp_dir().join(format!("grepr-channel-{}.txt", std::process::id()));
+        fs::write(&path, "a test\nnothing\nanother test\n").unwrap();
+        let args = CommandArgs::new("test".to_string(), path.clone(), false, false, false, false);
+        let (results, handle) = search_channel(args);
+        let results: Vec<OwnedMatch> = results.iter().collect();
+        let stats = handle.join().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(results.iter().map(|m| m.number).collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(results[1].span, Some(Span { start: 8, end: 12 }));
+        assert_eq!(stats.unwrap().lines.lines_matched, 2)
+    }
+
+    #[test]
+    fn test_search_channel_binary_max_total() {
+        let path = std::env::temp_dir().join(format!("grepr-channel-binary-{}.bin", std::process::id()));
+        fs::write(&path, b"\xde\xad\nx\n\x00\xde\xad\n\xde\xad\n").unwrap();
+        let args = CommandArgs::new(r"\xde\xad".to_string(), path.clone(), false, false, false, false)
+            .with_binary(true)
+            .with_max_total(Some(2));
+        let (results, handle) = search_channel(args.clone());
+        let results: Vec<usize> = results.iter().map(|m| m.number).collect();
+        handle.join().unwrap().unwrap();
+        let contents = args.read().unwrap();
+        let expected: Vec<usize> = crate::search_str(&contents, &binary_patterns(&args).unwrap()).unwrap().into_iter().map(|m| m.0).collect();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(results, expected);
+        assert_eq!(results, vec![0, 2])
+    }
+}
@@ -0,0 +1,496 @@
+//! Default options loaded from a configuration file.
+//!
+//! The file is read from `$GREPR_CONFIG` if set, otherwise from
+//! `$XDG_CONFIG_HOME/grepr/config.toml` or `~/.config/grepr/config.toml`.
+//! Values in the file only fill in options that were not given on the
+//! command line. A flag the file turns on can be turned off again with
+//! its `--no-` form, such as `--no-word`, or `--case-sensitive` for
+//! `ignore-case`.
+//!
+//! ```toml
+//! ignore-case = true
+//! match-color = "blue"
+//! engine = "regex"
+//!
+//! [profile.logs]
+//! word = true
+//! context = 2
+//! ```
+//!
+//! The keys are the long names of these options: `ignore-case`,
+//! `ignore-accents`, `invert-match`, `word`, `line`, `progress`,
+//! `match-color`, `engine`, `format`, `lang`, `context`, `max-columns`,
+//! `tabs`, `line-number-start`, `hyperlink-format` and `time-format`.
+//! Values are written as on the command line. `format` only applies when
+//! the command line asks for no other output, such as `--field` or `-o`,
+//! and `engine` doesn't apply to `--fuzzy` searches.
+//!
+//! A profile is selected with `--profile <NAME>`, and its options take
+//! precedence over the top-level ones, including `false` over `true`.
+//!
+//! Extra arguments can also be given in the `GREPR_OPTS` environment
+//! variable. They are inserted before the real command line arguments,
+//! so anything given on the command line still wins.
+//!
+//! Keys that grepr doesn't know are ignored when loading, so a typo
+//! quietly has no effect. `grepr --check-config` reports them instead.
+use std::collections::BTreeMap;
+use std::env;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer};
+use crate::{check_color, CommandArgs, Engine, Lang, OutputFormat};
+
+
+// The keys allowed at the top level and in each profile.
+const KEYS: [&str; 16] = [
+    "ignore-case", "ignore-accents", "invert-match", "word", "line", "progress", "match-color",
+    "engine", "format", "lang", "context", "max-columns", "tabs", "line-number-start", "hyperlink-format", "time-format",
+];
+
+/// Options read from a grepr configuration file.
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Config {
+    /// Options that apply to every search.
+    #[serde(flatten)]
+    pub defaults: Defaults,
+    /// Named sets of options selected with `--profile`.
+    pub profile: BTreeMap<String, Defaults>,
+}
+
+/// A set of default options, either top-level or from a profile.
+/// 
+/// Options the file leaves out are `None`, so a profile can tell them
+/// apart from options it turns off.
+/// 
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Defaults {
+    /// Ignores case while searching.
+    pub ignore_case: Option<bool>,
+    /// Ignores accents and other diacritics while searching.
+    pub ignore_accents: Option<bool>,
+    /// Inverts search results.
+    pub invert_match: Option<bool>,
+    /// Matches exact words only.
+    pub word: Option<bool>,
+    /// Matches exact lines only.
+    pub line: Option<bool>,
+    /// Shows a progress bar while searching.
+    pub progress: Option<bool>,
+    /// Color used to highlight matches.
+    pub match_color: Option<String>,
+    /// How the query and other patterns are matched.
+    #[serde(deserialize_with = "value_enum")]
+    pub engine: Option<Engine>,
+    /// Output format for the results.
+    #[serde(deserialize_with = "value_enum")]
+    pub format: Option<OutputFormat>,
+    /// Language of messages and `--stats` output.
+    #[serde(deserialize_with = "value_enum")]
+    pub lang: Option<Lang>,
+    /// Lines printed before and after each match.
+    pub context: Option<usize>,
+    /// Printed lines longer than this many characters are omitted.
+    pub max_columns: Option<usize>,
+    /// Tabs in printed lines are expanded to stops this many columns apart.
+    pub tabs: Option<usize>,
+    /// Number printed for the first line.
+    pub line_number_start: Option<usize>,
+    /// Makes file names and line numbers terminal hyperlinks.
+    pub hyperlink_format: Option<String>,
+    /// Format of the timestamp at the start of each line.
+    pub time_format: Option<String>,
+}
+
+impl Config {
+    /// Loads the configuration file, if there is one.
+    ///
+    /// # Returns
+    /// Returns `None` if no configuration file exists. A file named by
+    /// `GREPR_CONFIG` that cannot be read is an error.
+    ///
+    pub fn load() -> Result<Option<Config>, Box<dyn Error>> {
+        let Some(path) = path() else {
+            return Ok(None);
+        };
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("could not read config file {}: {e}", path.display()))?;
+        Ok(Some(Config::parse(&contents)?))
+    }
+
+    /// Parses the contents of a configuration file.
+    pub fn parse(contents: &str) -> Result<Config, Box<dyn Error>> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Fills in any options of `args` that were not set on the command line.
+    /// 
+    /// Options from the profile named by `--profile` take precedence over
+    /// the top-level defaults.
+    /// 
+    /// # Returns
+    /// Returns an error if the selected profile is not in the file.
+    /// 
+    pub fn apply(&self, args: &mut CommandArgs) -> Result<(), Box<dyn Error>> {
+        let defaults = match &args.profile {
+            Some(name) => self.profile.get(name).ok_or_else(|| format!("unknown profile '{name}'"))?.or(&self.defaults),
+            None => self.defaults.clone(),
+        };
+        defaults.apply(args);
+        Ok(())
+    }
+}
+
+impl Defaults {
+    /// Returns these options, with any left out taken from `fallback`.
+    pub fn or(&self, fallback: &Defaults) -> Defaults {
+        Defaults {
+            ignore_case: self.ignore_case.or(fallback.ignore_case),
+            ignore_accents: self.ignore_accents.or(fallback.ignore_accents),
+            invert_match: self.invert_match.or(fallback.invert_match),
+            word: self.word.or(fallback.word),
+            line: self.line.or(fallback.line),
+            progress: self.progress.or(fallback.progress),
+            match_color: self.match_color.clone().or_else(|| fallback.match_color.clone()),
+            engine: self.engine.or(fallback.engine),
+            format: self.format.or(fallback.format),
+            lang: self.lang.or(fallback.lang),
+            context: self.context.or(fallback.context),
+            max_columns: self.max_columns.or(fallback.max_columns),
+            tabs: self.tabs.or(fallback.tabs),
+            line_number_start: self.line_number_start.or(fallback.line_number_start),
+            hyperlink_format: self.hyperlink_format.clone().or_else(|| fallback.hyperlink_format.clone()),
+            time_format: self.time_format.clone().or_else(|| fallback.time_format.clone()),
+        }
+    }
+
+    /// Fills in any options of `args` that were not set on the command line.
+    /// 
+    /// A flag given on the command line stays on, and one turned off with
+    /// its `--no-` form, or `--case-sensitive`, stays off. The format is
+    /// left alone if the command line picks another kind of output, and
+    /// the engine if it asks for `--fuzzy`.
+    /// 
+    pub fn apply(&self, args: &mut CommandArgs) {
+        args.ignore_case = resolve(args.ignore_case, args.case_sensitive, self.ignore_case);
+        args.ignore_accents = resolve(args.ignore_accents, args.no_ignore_accents, self.ignore_accents);
+        args.invert_match = resolve(args.invert_match, args.no_invert_match, self.invert_match);
+        args.word = resolve(args.word, args.no_word, self.word);
+        args.line = resolve(args.line, args.no_line, self.line);
+        args.progress = resolve(args.progress, args.no_progress, self.progress);
+        if args.match_color.is_none() {
+            args.match_color = self.match_color.clone();
+        }
+        if args.fuzzy.is_none() {
+            args.engine = args.engine.or(self.engine);
+        }
+        let other_output = args.field.is_some() || args.hex_context.is_some() || args.format_template.is_some()
+            || args.diff || args.only_matching || args.replace.is_some() || args.filename_only
+            || args.count_matches || args.count_per_pattern || args.files_with_all_matches || args.json_input || args.frequencies;
+        if !other_output {
+            args.format = args.format.or(self.format);
+        }
+        args.lang = args.lang.or(self.lang);
+        args.context = args.context.or(self.context);
+        args.max_columns = args.max_columns.or(self.max_columns);
+        args.tabs = args.tabs.or(self.tabs);
+        args.line_number_start = args.line_number_start.or(self.line_number_start);
+        args.hyperlink_format = args.hyperlink_format.clone().or_else(|| self.hyperlink_format.clone());
+        args.time_format = args.time_format.clone().or_else(|| self.time_format.clone());
+    }
+}
+
+// Reads an option such as `engine` by the name its value has on the
+// command line.
+fn value_enum<'de, D: Deserializer<'de>, T: ValueEnum>(deserializer: D) -> Result<Option<T>, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    T::from_str(&name, false).map(Some).map_err(|_| {
+        let names: Vec<String> = T::value_variants().iter().filter_map(|v| Some(v.to_possible_value()?.get_name().to_string())).collect();
+        serde::de::Error::custom(format!("invalid value '{name}', expected one of: {}", names.join(", ")))
+    })
+}
+
+// Returns the value of a flag that is on if given on the command line,
+// off if turned off there, and otherwise as the config file sets it.
+fn resolve(on: bool, off: bool, default: Option<bool>) -> bool {
+    on || !off && default.unwrap_or(false)
+}
+
+/// Returns the path of the configuration file, if there is one.
+///
+/// This is `GREPR_CONFIG` if set, even if nothing exists there, and
+/// otherwise the default location if a file exists there.
+///
+pub fn path() -> Option<PathBuf> {
+    match env::var_os("GREPR_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => default_path().filter(|path| path.is_file()),
+    }
+}
+
+/// Checks the contents of a configuration file for mistakes.
+///
+/// Reports unknown keys and tables, which loading silently ignores, and
+/// match colors that can't be used.
+///
+/// # Returns
+/// Returns one message per problem, starting with its line number, or
+/// an error if the contents can't be parsed at all.
+///
+/// # Example
+/// ```
+/// # use crate::grepr::config::check;
+/// let problems = check("ignore-case = true\nword-regexp = true\n").unwrap();
+///
+/// assert_eq!(problems, vec!["line 2: unknown key 'word-regexp'"]);
+/// ```
+///
+pub fn check(contents: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let config = Config::parse(contents)?;
+    let mut problems = Vec::new();
+    let mut table = Some(&config.defaults);
+    for (index, line) in contents.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let name = header.split(']').next().unwrap_or_default().trim();
+            table = name.strip_prefix("profile.").and_then(|profile| config.profile.get(profile.trim_matches('"')));
+            if table.is_none() {
+                problems.push(format!("line {number}: unknown table [{name}]"));
+            }
+            continue;
+        }
+        let (Some(defaults), Some((key, _))) = (table, line.split_once('=')) else {
+            continue;
+        };
+        match key.trim() {
+            _ if line.starts_with('#') => {}
+            "match-color" => {
+                let color = defaults.match_color.as_deref().unwrap_or_default();
+                if let Err(e) = check_color(color) {
+                    problems.push(format!("line {number}: {e}"));
+                }
+            }
+            key if KEYS.contains(&key) => {}
+            key => problems.push(format!("line {number}: unknown key '{key}'")),
+        }
+    }
+    Ok(problems)
+}
+
+/// Inserts the arguments from `GREPR_OPTS` after the program name.
+///
+/// # Returns
+/// Returns the combined arguments, or an error if `GREPR_OPTS` has an
+/// unterminated quote.
+///
+pub fn with_env_opts(args: impl IntoIterator<Item = OsString>) -> Result<Vec<OsString>, Box<dyn Error>> {
+    let opts = env::var("GREPR_OPTS").unwrap_or_default();
+    let opts = split_opts(&opts).map_err(|e| format!("invalid GREPR_OPTS: {e}"))?;
+    let mut args = args.into_iter();
+    let mut combined: Vec<OsString> = args.next().into_iter().collect();
+    combined.extend(opts.into_iter().map(OsString::from));
+    combined.extend(args);
+    Ok(combined)
+}
+
+/// Splits a string of options into arguments.
+///
+/// Arguments are separated by whitespace. Single or double quotes keep
+/// whitespace inside an argument, and a backslash escapes the next
+/// character outside of single quotes.
+///
+pub fn split_opts(opts: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut quote = None;
+    let mut chars = opts.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('\''), c) => current.push(c),
+            (_, '\\') => {
+                current.extend(chars.next());
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err("unterminated quote".into());
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+// Returns the default location of the configuration file.
+fn default_path() -> Option<PathBuf> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(config_dir.join("grepr").join("config.toml"))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_parse() {
+        let config = Config::parse("ignore-case = true\nmatch-color = \"blue\"\n").unwrap();
+
+        assert_eq!(config.defaults.ignore_case, Some(true));
+        assert_eq!(config.defaults.word, None);
+        assert_eq!(config.defaults.match_color.as_deref(), Some("blue"))
+    }
+
+    #[test]
+    fn test_config_apply_cli_overrides() {
+        let config = Config::parse("word = true\nmatch-color = \"blue\"\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        args.match_color = Some("green".to_string());
+        config.apply(&mut args).unwrap();
+
+        assert!(args.word);
+        assert!(!args.ignore_case);
+        assert_eq!(args.match_color.as_deref(), Some("green"))
+    }
+
+    #[test]
+    fn test_config_apply_case_sensitive() {
+        let config = Config::parse("ignore-case = true\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_case_sensitive(true);
+        config.apply(&mut args).unwrap();
+
+        assert!(!args.ignore_case)
+    }
+
+    #[test]
+    fn test_config_apply_profile() {
+        let config = Config::parse("match-color = \"blue\"\n[profile.logs]\nword = true\nmatch-color = \"red\"\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        args.profile = Some("logs".to_string());
+        config.apply(&mut args).unwrap();
+
+        assert!(args.word);
+        assert_eq!(args.match_color.as_deref(), Some("red"))
+    }
+
+    #[test]
+    fn test_config_apply_profile_turns_off() {
+        let config = Config::parse("ignore-case = true\nword = true\n[profile.exact]\nignore-case = false\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        args.profile = Some("exact".to_string());
+        config.apply(&mut args).unwrap();
+
+        assert!(!args.ignore_case);
+        assert!(args.word)
+    }
+
+    #[test]
+    fn test_config_apply_no_flags() {
+        let config = Config::parse("word = true\ninvert-match = true\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        args.no_word = true;
+        config.apply(&mut args).unwrap();
+
+        assert!(!args.word);
+        assert!(args.invert_match)
+    }
+
+    #[test]
+    fn test_config_apply_options() {
+        let config = Config::parse("engine = \"default\"\nformat = \"csv\"\ncontext = 2\nline-number-start = 0\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_line_number_start(5);
+        config.apply(&mut args).unwrap();
+
+        assert_eq!(args.engine, Some(Engine::Literal));
+        assert_eq!(args.format, Some(OutputFormat::Csv));
+        assert_eq!(args.context, Some(2));
+        assert_eq!(args.line_number_start, Some(5))
+    }
+
+    #[test]
+    fn test_config_apply_format_other_output() {
+        let config = Config::parse("format = \"sarif\"\nengine = \"regex\"\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_fuzzy(Some(1));
+        args.field = Some(2);
+        config.apply(&mut args).unwrap();
+
+        assert_eq!(args.format, None);
+        assert_eq!(args.engine, None)
+    }
+
+    #[test]
+    fn test_config_parse_bad_value() {
+        let e = Config::parse("engine = \"perl\"\n").unwrap_err();
+
+        assert!(e.to_string().contains("invalid value 'perl'"))
+    }
+
+    #[test]
+    fn test_config_apply_unknown_profile() {
+        let config = Config::parse("[profile.logs]\nword = true\n").unwrap();
+        let mut args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        args.profile = Some("code".to_string());
+
+        assert!(config.apply(&mut args).is_err())
+    }
+
+    #[test]
+    fn test_split_opts_quotes() {
+        let args = split_opts(r#"-i  --match-color 'light blue' "a b"\ c ''"#).unwrap();
+
+        assert_eq!(args, vec!["-i", "--match-color", "light blue", "a b c", ""])
+    }
+
+    #[test]
+    fn test_split_opts_unterminated() {
+        assert!(split_opts("--match-color 'blue").is_err())
+    }
+
+    #[test]
+    fn test_check_problems() {
+        let contents = "ignore_case = true\nmatch-color = \"blu\"\n# word = true\n[profile.logs]\nwrod = true\n[profiles.code]\nline = true\n";
+        let problems = check(contents).unwrap();
+
+        assert_eq!(problems, vec![
+            "line 1: unknown key 'ignore_case'",
+            "line 2: unknown color 'blu'",
+            "line 5: unknown key 'wrod'",
+            "line 6: unknown table [profiles.code]",
+        ])
+    }
+
+    #[test]
+    fn test_check_invalid_toml() {
+        assert!(check("word = yes\n").is_err())
+    }
+}
@@ -0,0 +1,225 @@
+//! A C ABI for the search engine.
+//!
+//! Only available with the `ffi` feature. Options and results are opaque
+//! handles that must be released with their matching `_free` function.
+//! All strings passed in and out are NUL-terminated UTF-8. The matching
+//! declarations for C callers are in `include/grepr.h`.
+use std::ffi::{c_char, CStr, CString};
+use std::path::PathBuf;
+use std::ptr;
+use crate::{search_str, CommandArgs};
+
+
+/// Opaque search options for C callers.
+pub struct GreprOptions {
+    args: CommandArgs,
+}
+
+/// Opaque search results for C callers.
+pub struct GreprResults {
+    lines: Vec<(usize, CString)>,
+}
+
+/// Creates a new set of options for `query` with every flag turned off.
+///
+/// # Returns
+/// Returns a handle to free with `grepr_options_free`, or null if
+/// `query` is null or not valid UTF-8.
+///
+/// # Safety
+/// `query` must be null or point to a NUL-terminated string.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_options_new(query: *const c_char) -> *mut GreprOptions {
+    if query.is_null() {
+        return ptr::null_mut();
+    }
+    match CStr::from_ptr(query).to_str() {
+        Ok(query) => {
+            let args = CommandArgs::new(query.to_string(), PathBuf::new(), false, false, false, false);
+            Box::into_raw(Box::new(GreprOptions { args }))
+        }
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Sets whether case is ignored while searching.
+///
+/// # Safety
+/// `options` must be a handle returned by `grepr_options_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_options_set_ignore_case(options: *mut GreprOptions, value: bool) {
+    if let Some(options) = options.as_mut() {
+        options.args.ignore_case = value;
+    }
+}
+
+/// Sets whether the search results are inverted.
+///
+/// # Safety
+/// `options` must be a handle returned by `grepr_options_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_options_set_invert_match(options: *mut GreprOptions, value: bool) {
+    if let Some(options) = options.as_mut() {
+        options.args.invert_match = value;
+    }
+}
+
+/// Sets whether only exact words are matched.
+///
+/// # Safety
+/// `options` must be a handle returned by `grepr_options_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_options_set_word(options: *mut GreprOptions, value: bool) {
+    if let Some(options) = options.as_mut() {
+        options.args.word = value;
+    }
+}
+
+/// Sets whether only exact lines are matched.
+///
+/// # Safety
+/// `options` must be a handle returned by `grepr_options_new`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_options_set_line(options: *mut GreprOptions, value: bool) {
+    if let Some(options) = options.as_mut() {
+        options.args.line = value;
+    }
+}
+
+/// Releases options created by `grepr_options_new`.
+///
+/// # Safety
+/// `options` must be null or a handle returned by `grepr_options_new`
+/// that has not already been freed.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_options_free(options: *mut GreprOptions) {
+    if !options.is_null() {
+        drop(Box::from_raw(options));
+    }
+}
+
+/// Searches `contents` using `options`.
+///
+/// # Returns
+/// Returns a handle to free with `grepr_results_free`, or null if either
+/// argument is null, `contents` is not valid UTF-8 or the search fails.
+///
+/// # Safety
+/// `options` must be a handle returned by `grepr_options_new` and
+/// `contents` must be null or point to a NUL-terminated string.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_search(options: *const GreprOptions, contents: *const c_char) -> *mut GreprResults {
+    let Some(options) = options.as_ref() else {
+        return ptr::null_mut();
+    };
+    if contents.is_null() {
+        return ptr::null_mut();
+    }
+    let Ok(contents) = CStr::from_ptr(contents).to_str() else {
+        return ptr::null_mut();
+    };
+    let Ok(results) = search_str(contents, &options.args) else {
+        return ptr::null_mut();
+    };
+    // Lines come from a C string, so they can never contain a NUL.
+    let lines = results
+        .into_iter()
+        .map(|(number, line)| (number, CString::new(line).unwrap()))
+        .collect();
+    Box::into_raw(Box::new(GreprResults { lines }))
+}
+
+/// Returns the number of lines in a set of results.
+///
+/// # Safety
+/// `results` must be null or a handle returned by `grepr_search`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_results_len(results: *const GreprResults) -> usize {
+    results.as_ref().map_or(0, |results| results.lines.len())
+}
+
+/// Returns the line number of the result at `index`.
+///
+/// # Safety
+/// `results` must be a handle returned by `grepr_search` and `index`
+/// must be less than `grepr_results_len`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_results_line_number(results: *const GreprResults, index: usize) -> usize {
+    (&*results).lines[index].0
+}
+
+/// Returns the text of the result at `index`.
+///
+/// The string is owned by `results` and is valid until it is freed.
+///
+/// # Safety
+/// `results` must be a handle returned by `grepr_search` and `index`
+/// must be less than `grepr_results_len`.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_results_line(results: *const GreprResults, index: usize) -> *const c_char {
+    (&*results).lines[index].1.as_ptr()
+}
+
+/// Releases results created by `grepr_search`.
+///
+/// # Safety
+/// `results` must be null or a handle returned by `grepr_search` that
+/// has not already been freed.
+///
+#[no_mangle]
+pub unsafe extern "C" fn grepr_results_free(results: *mut GreprResults) {
+    if !results.is_null() {
+        drop(Box::from_raw(results));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grepr_search_word_nocase() {
+        let query = CString::new("ANOTHER").unwrap();
+        let contents = CString::new("this is a test.\nthis is another test!").unwrap();
+
+        unsafe {
+            let options = grepr_options_new(query.as_ptr());
+            grepr_options_set_ignore_case(options, true);
+            grepr_options_set_word(options, true);
+            let results = grepr_search(options, contents.as_ptr());
+
+            assert_eq!(grepr_results_len(results), 1);
+            assert_eq!(grepr_results_line_number(results, 0), 1);
+            assert_eq!(CStr::from_ptr(grepr_results_line(results, 0)).to_str(), Ok("this is another test!"));
+
+            grepr_results_free(results);
+            grepr_options_free(options);
+        }
+    }
+
+    #[test]
+    fn test_grepr_search_null_contents() {
+        let query = CString::new("test").unwrap();
+
+        unsafe {
+            let options = grepr_options_new(query.as_ptr());
+            let results = grepr_search(options, ptr::null());
+
+            assert!(results.is_null());
+            assert_eq!(grepr_results_len(results), 0);
+
+            grepr_options_free(options);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Finding the function or class around a match in source code.
+//!
+//! Used for `--show-function` and `--md-scope`. Headings are found with
+//! one line-based heuristic per language, picked by file extension,
+//! rather than by parsing. Markdown section titles are found the same
+//! way as for `--md-scope headings`. The heading of a line is the
+//! nearest heading line above it.
+use std::path::Path;
+use regex::Regex;
+use crate::markdown::ScopeFilter;
+
+
+// Returns the pattern for heading lines in the language of `path`, or
+// `None` if the extension isn't recognized.
+fn heading_pattern(path: &Path) -> Option<Regex> {
+    let pattern = match path.extension()?.to_str()? {
+        "rs" => r#"^\s*(pub(\([^)]*\))?\s+)?((async|const|unsafe|extern\s+"[^"]*")\s+)*(fn|impl|struct|enum|trait|mod|macro_rules!)\b"#,
+        "py" | "pyi" => r"^\s*(async\s+)?(def|class)\s",
+        "js" | "jsx" | "mjs" | "cjs" | "ts" | "tsx" => r"^\s*(export\s+)?(default\s+)?(async\s+)?(function\b|class\s)",
+        "go" => r"^(func|type)\s",
+        "rb" => r"^\s*(def|class|module)\s",
+        "java" | "cs" | "kt" => r"^\s*(((public|private|protected|internal|static|final|abstract|sealed|override|virtual|async)\s+)+[\w<>\[\],. ]+\s+\w+\s*\(|((public|private|protected|internal|static|final|abstract|sealed|data)\s+)*(class|interface|enum|record|object)\s)",
+        "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" => r"^[A-Za-z_][\w\s\*&:<>,]*\([^;]*$",
+        _ => return None,
+    };
+    Some(Regex::new(pattern).unwrap())
+}
+
+// Returns the line number and text of every heading in `contents`, in
+// order, or an empty list if the language of `path` isn't recognized.
+pub(crate) fn headings<'a>(path: &Path, contents: &'a str) -> Vec<(usize, &'a str)> {
+    if path.extension().is_some_and(|extension| extension == "md" || extension == "markdown") {
+        let mut sections = ScopeFilter::headings();
+        return contents.lines().enumerate().filter(|(_, line)| sections.accepts(line)).collect();
+    }
+    let Some(pattern) = heading_pattern(path) else {
+        return Vec::new();
+    };
+    contents.lines().enumerate().filter(|(_, line)| pattern.is_match(line)).collect()
+}
+
+// Returns the heading above line `number`, given every heading in order.
+// A heading line is not its own heading.
+pub(crate) fn heading_of<'h, 'a>(headings: &'h [(usize, &'a str)], number: usize) -> Option<&'h (usize, &'a str)> {
+    let above = headings.partition_point(|&(heading, _)| heading < number);
+    above.checked_sub(1).map(|index| &headings[index])
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_headings_rust() {
+        let contents = "use std::fs;\n\npub(crate) async fn load() {\n    read();\n}\nimpl Search {\n    fn find(&self) {}\n}";
+        let found = headings(Path::new("src/lib.rs"), contents);
+
+        assert_eq!(found, vec![(2, "pub(crate) async fn load() {"), (5, "impl Search {"), (6, "    fn find(&self) {}")]);
+        assert_eq!(heading_of(&found, 3), Some(&(2, "pub(crate) async fn load() {")));
+        assert_eq!(heading_of(&found, 2), None)
+    }
+
+    #[test]
+    fn test_headings_unknown_language() {
+        assert!(headings(Path::new("notes.txt"), "def main():").is_empty())
+    }
+}
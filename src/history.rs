@@ -0,0 +1,102 @@
+//! Searching the versions of a file in git history.
+//!
+//! Shells out to `git` to list the commits that touched a file and read
+//! the file as it was at each of them. The file must be inside a git
+//! work tree, and `git` must be on the `PATH`.
+use std::error::Error;
+use std::path::Path;
+use std::process::Command;
+use crate::{search_str, CommandArgs};
+
+
+/// A matching line from a version of a file in git history.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HistoryMatch {
+    /// The abbreviated hash of the commit.
+    pub commit: String,
+    /// The line number within the file at that commit.
+    pub number: usize,
+    /// The contents of the line.
+    pub line: String,
+}
+
+/// Searches every committed version of the file at `args.path`.
+///
+/// Commits are searched from newest to oldest. Commits where the file
+/// was deleted are skipped.
+///
+/// # Returns
+/// Returns the matching lines of every version, or an error if `git`
+/// fails to list the file's history.
+///
+pub fn search_history(args: &CommandArgs) -> Result<Vec<HistoryMatch>, Box<dyn Error>> {
+    let (dir, file) = split_path(&args.path)?;
+    let log = git(dir, &["log", "--format=%h", "--", &file])?;
+    let mut matches = Vec::new();
+    for commit in log.lines() {
+        let Ok(contents) = git(dir, &["show", &format!("{commit}:{file}")]) else {
+            continue;
+        };
+        for (number, line) in search_str(&contents, args)? {
+            matches.push(HistoryMatch { commit: commit.to_string(), number, line: line.to_string() });
+        }
+    }
+    Ok(matches)
+}
+
+// Splits a path into the directory git should run in and the file name
+// relative to it, in the `./name` form `git show` resolves from there.
+fn split_path(path: &Path) -> Result<(&Path, String), Box<dyn Error>> {
+    let file = path.file_name().ok_or_else(|| format!("{} is not a file", path.display()))?;
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Ok((dir, format!("./{}", file.to_string_lossy())))
+}
+
+// Runs git in `dir` and returns its output.
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_search_history_removed_line() {
+        let dir = std::env::temp_dir().join(format!("grepr-history-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let commit = |contents: &str| {
+            fs::write(dir.join("notes.txt"), contents).unwrap();
+            let config = ["-c", "user.name=grepr", "-c", "user.email=grepr@example.com"];
+            git(&dir, &[&config[..], &["commit", "-qam", "update"]].concat()).unwrap();
+        };
+        git(&dir, &["init", "-q"]).unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        git(&dir, &["add", "notes.txt"]).unwrap();
+        commit("keep\nsecret token\n");
+        commit("keep\n");
+
+        let args = CommandArgs::new("secret".to_string(), dir.join("notes.txt"), false, false, false, false);
+        let matches = search_history(&args);
+        fs::remove_dir_all(&dir).unwrap();
+        let matches = matches.unwrap();
+
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, "secret token")
+    }
+
+    #[test]
+    fn test_split_path_relative() {
+        let path = PathBuf::from("notes.txt");
+
+        assert_eq!(split_path(&path).unwrap(), (Path::new("."), "./notes.txt".to_string()))
+    }
+}
@@ -0,0 +1,191 @@
+//! Searching contents that arrive in chunks.
+//!
+//! For network streams and readers that can't expose `BufRead`, bytes
+//! are pushed in whatever pieces they arrive in. A line split across two
+//! chunks is held back until its end arrives, so the matching rules are
+//! the same as `Search::find`. Each line is decoded as `read` would
+//! decode the whole file, so `binary` and `strip_ansi` apply, and no
+//! more than `max_total` matches are returned.
+use std::error::Error;
+use std::mem;
+use memchr::memrchr;
+use crate::{decode, CommandArgs, CompiledQuery, LineSearch, OwnedMatch, Stats};
+
+
+/// A search fed with chunks of bytes by `push_bytes`.
+pub struct IncrementalSearch<'q> {
+    query: CompiledQuery<'q>,
+    lines: LineSearch,
+    // The start of a line whose end hasn't been pushed yet.
+    partial: Vec<u8>,
+    number: usize,
+    found: usize,
+    stats: Stats,
+}
+
+impl<'q> IncrementalSearch<'q> {
+    /// Creates a search for the query and options in `args`.
+    ///
+    /// The path in `args` is ignored.
+    ///
+    /// # Returns
+    /// Returns an error if the query or an option such as `--since` is
+    /// invalid, or a `binary` pattern has a bad `\xNN` escape.
+    ///
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, IncrementalSearch};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+    /// let mut search = IncrementalSearch::new(&args).unwrap();
+    ///
+    /// assert!(search.push_bytes(b"nothing\na te").unwrap().is_empty());
+    /// let matches = search.push_bytes(b"st\nlast test").unwrap();
+    /// assert_eq!(matches[0].line, "a test");
+    /// assert_eq!(search.finish().unwrap()[0].number, 2);
+    /// ```
+    ///
+    pub fn new(args: &'q CommandArgs) -> Result<IncrementalSearch<'q>, Box<dyn Error>> {
+        let query = CompiledQuery::decoded(args)?;
+        Ok(IncrementalSearch {
+            lines: LineSearch::new(&query),
+            query,
+            partial: Vec::new(),
+            number: 0,
+            found: 0,
+            stats: Stats::default(),
+        })
+    }
+
+    /// Searches the next chunk of the contents.
+    ///
+    /// Chunks can end anywhere, even inside a line or a UTF-8 character.
+    ///
+    /// # Returns
+    /// Returns the lines completed by this chunk that match, or an error
+    /// if one of them is not valid UTF-8.
+    ///
+    pub fn push_bytes(&mut self, bytes: &[u8]) -> Result<Vec<OwnedMatch>, Box<dyn Error>> {
+        let Some(last_newline) = memrchr(b'\n', bytes) else {
+            self.partial.extend_from_slice(bytes);
+            return Ok(Vec::new());
+        };
+        let mut lines = mem::take(&mut self.partial);
+        lines.extend_from_slice(&bytes[..last_newline]);
+        self.partial.extend_from_slice(&bytes[last_newline + 1..]);
+        let mut matches = Vec::new();
+        for line in lines.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            self.search_line(line, &mut matches)?;
+        }
+        Ok(matches)
+    }
+
+    /// Searches the last line, if the contents didn't end with a line
+    /// break.
+    ///
+    /// Call this once the stream has ended. Bytes pushed afterwards
+    /// start a new line.
+    ///
+    /// # Returns
+    /// Returns the last line if it matches, or an error if it is not
+    /// valid UTF-8.
+    ///
+    pub fn finish(&mut self) -> Result<Vec<OwnedMatch>, Box<dyn Error>> {
+        let mut matches = Vec::new();
+        if !self.partial.is_empty() {
+            let line = mem::take(&mut self.partial);
+            self.search_line(&line, &mut matches)?;
+        }
+        Ok(matches)
+    }
+
+    /// Returns the counts collected from the lines searched so far.
+    pub fn get_stats(&self) -> Stats {
+        self.stats
+    }
+
+    // Searches one complete line, without its line break.
+    fn search_line(&mut self, line: &[u8], matches: &mut Vec<OwnedMatch>) -> Result<(), Box<dyn Error>> {
+        let args = self.query.args;
+        let number = self.number;
+        self.number += 1;
+        if args.max_total.is_some_and(|max| self.found >= max) {
+            return Ok(());
+        }
+        if args.line_range.is_some_and(|range| !range.contains(number)) {
+            return Ok(());
+        }
+        let line = decode(args, line.to_vec()).map_err(|e| format!("line {number}: {e}"))?;
+        if let Some(span) = self.lines.select(&self.query, &line, &mut self.stats) {
+            matches.push(OwnedMatch { number, line, span });
+            self.found += 1;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::{search_str, Span};
+
+    #[test]
+    fn test_push_bytes_matches_search_str() {
+        let args = CommandArgs::new("é".to_string(), PathBuf::new(), false, false, false, false);
+        let contents = "café\r\nnone\n\nrésumé\nlast é";
+        let mut search = IncrementalSearch::new(&args).unwrap();
+        let mut matches = Vec::new();
+        // Splits every line, and the two bytes of each "é".
+        for chunk in contents.as_bytes().chunks(3) {
+            matches.extend(search.push_bytes(chunk).unwrap());
+        }
+        matches.extend(search.finish().unwrap());
+        let expected = search_str(contents, &args).unwrap();
+
+        assert_eq!(matches.iter().map(|m| (m.number, m.line.as_str())).collect::<Vec<_>>(), expected);
+        assert_eq!(matches[0].span, Some(Span { start: 3, end: 5 }));
+        assert_eq!(search.get_stats().lines_searched, 5)
+    }
+
+    #[test]
+    fn test_push_bytes_decoded_matches_search_str() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_strip_ansi(true)
+            .with_max_total(Some(2));
+        let contents = "a \u{1b}[31mte\u{1b}[0mst\r\nno\ntest 2\ntest 3\n";
+        let mut search = IncrementalSearch::new(&args).unwrap();
+        let mut matches = Vec::new();
+        for chunk in contents.as_bytes().chunks(4) {
+            matches.extend(search.push_bytes(chunk).unwrap());
+        }
+        matches.extend(search.finish().unwrap());
+        let stripped = crate::strip_ansi(contents);
+        let expected = search_str(&stripped, &args).unwrap();
+
+        assert_eq!(matches.iter().map(|m| (m.number, m.line.as_str())).collect::<Vec<_>>(), expected);
+        assert_eq!(expected.iter().map(|m| m.0).collect::<Vec<_>>(), vec![0, 2])
+    }
+
+    #[test]
+    fn test_push_bytes_binary() {
+        let args = CommandArgs::new(r"\xde\xad".to_string(), PathBuf::new(), false, false, false, false)
+            .with_binary(true);
+        let mut search = IncrementalSearch::new(&args).unwrap();
+        let mut matches = search.push_bytes(b"text\n\x00\xde").unwrap();
+        matches.extend(search.push_bytes(b"\xad\xff").unwrap());
+        matches.extend(search.finish().unwrap());
+
+        assert_eq!(matches.iter().map(|m| m.number).collect::<Vec<_>>(), vec![1])
+    }
+
+    #[test]
+    fn test_push_bytes_invalid_utf8() {
+        let args = CommandArgs::new("a".to_string(), PathBuf::new(), false, false, false, false);
+        let mut search = IncrementalSearch::new(&args).unwrap();
+
+        assert!(search.push_bytes(b"a\n\xff\n").is_err())
+    }
+}
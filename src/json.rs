@@ -0,0 +1,221 @@
+//! Matching against the values of JSON documents.
+//!
+//! Used for `--json-input`. Each line is parsed as a JSON document and
+//! flattened into its scalar values, each paired with the JSON pointer
+//! (RFC 6901) that leads to it. Strings are matched by their decoded
+//! text; numbers, booleans and null by how they are written.
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+
+/// A JSON value that matched a `--json-input` search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct JsonMatch {
+    /// Line number of the document within the contents.
+    pub number: usize,
+    /// JSON pointer to the value, such as `/request/status`.
+    pub pointer: String,
+    /// The value, decoded if it is a string.
+    pub value: String,
+}
+
+// A scalar value and the pointer to it, such as `/request/status`.
+pub(crate) type Scalar = (String, String);
+
+// Parses a JSON document and returns every scalar value in it, in the
+// order they appear.
+pub(crate) fn scalars(text: &str) -> Result<Vec<Scalar>, String> {
+    let mut parser = Parser { text, chars: text.char_indices().peekable() };
+    let mut values = Vec::new();
+    parser.value(&mut String::new(), &mut values)?;
+    parser.whitespace();
+    match parser.chars.next() {
+        None => Ok(values),
+        Some((index, _)) => Err(format!("unexpected text at byte {index}")),
+    }
+}
+
+struct Parser<'t> {
+    text: &'t str,
+    chars: Peekable<CharIndices<'t>>,
+}
+
+impl Parser<'_> {
+    // Parses the value starting at the next character. `pointer` is its
+    // path, and is restored before returning.
+    fn value(&mut self, pointer: &mut String, values: &mut Vec<Scalar>) -> Result<(), String> {
+        self.whitespace();
+        match self.chars.peek().copied() {
+            Some((_, '{')) => {
+                self.chars.next();
+                self.members('}', pointer, values, |parser| {
+                    let key = parser.string()?;
+                    parser.expect(':')?;
+                    Ok(key.replace('~', "~0").replace('/', "~1"))
+                })
+            }
+            Some((_, '[')) => {
+                self.chars.next();
+                let mut index = 0;
+                self.members(']', pointer, values, |_| {
+                    index += 1;
+                    Ok((index - 1).to_string())
+                })
+            }
+            Some((_, '"')) => {
+                let value = self.string()?;
+                values.push((pointer.clone(), value));
+                Ok(())
+            }
+            Some((start, _)) => {
+                let mut end = start;
+                while let Some(&(index, c)) = self.chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+                        break;
+                    }
+                    end = index + c.len_utf8();
+                    self.chars.next();
+                }
+                let token = &self.text[start..end];
+                let is_number = token.bytes().all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'+' | b'.' | b'e' | b'E'))
+                    && !token.starts_with('+')
+                    && token.parse::<f64>().is_ok();
+                if !(is_number || matches!(token, "true" | "false" | "null")) {
+                    return Err(format!("unexpected value at byte {start}"));
+                }
+                values.push((pointer.clone(), token.to_string()));
+                Ok(())
+            }
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    // Parses the members of an object or array up to `close`. `name`
+    // parses whatever comes before each value and returns the pointer
+    // segment for it.
+    fn members<F>(&mut self, close: char, pointer: &mut String, values: &mut Vec<Scalar>, mut name: F) -> Result<(), String>
+    where
+        F: FnMut(&mut Self) -> Result<String, String>,
+    {
+        self.whitespace();
+        if self.chars.next_if(|&(_, c)| c == close).is_some() {
+            return Ok(());
+        }
+        loop {
+            self.whitespace();
+            let segment = name(self)?;
+            let length = pointer.len();
+            pointer.push('/');
+            pointer.push_str(&segment);
+            self.value(pointer, values)?;
+            pointer.truncate(length);
+            self.whitespace();
+            match self.chars.next() {
+                Some((_, ',')) => continue,
+                Some((_, c)) if c == close => return Ok(()),
+                Some((index, _)) => return Err(format!("expected ',' or '{close}' at byte {index}")),
+                None => return Err("unexpected end of input".to_string()),
+            }
+        }
+    }
+
+    // Parses a string and decodes its escapes.
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(value),
+                Some((_, '\\')) => {
+                    let c = match self.chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c @ ('"' | '\\' | '/')) => c,
+                        _ => return Err("invalid escape in string".to_string()),
+                    };
+                    value.push(c);
+                }
+                Some((_, c)) => value.push(c),
+                None => return Err("unterminated string".to_string()),
+            }
+        }
+    }
+
+    // Decodes the digits of a `\u` escape, including the second half of
+    // a surrogate pair.
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let high = self.hex4()?;
+        if !(0xd800..0xdc00).contains(&high) {
+            return char::from_u32(high).ok_or_else(|| "invalid \\u escape".to_string());
+        }
+        if self.chars.next().map(|(_, c)| c) != Some('\\') || self.chars.next().map(|(_, c)| c) != Some('u') {
+            return Err("unpaired surrogate in \\u escape".to_string());
+        }
+        let low = self.hex4()?;
+        char::from_u32(0x10000 + ((high - 0xd800) << 10) + low.wrapping_sub(0xdc00))
+            .filter(|_| (0xdc00..0xe000).contains(&low))
+            .ok_or_else(|| "invalid surrogate pair in \\u escape".to_string())
+    }
+
+    // Reads the four hex digits of a `\u` escape.
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.by_ref().take(4).map(|(_, c)| c).collect();
+        match digits.len() == 4 && digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            true => Ok(u32::from_str_radix(&digits, 16).unwrap()),
+            false => Err("invalid \\u escape".to_string()),
+        }
+    }
+
+    // Skips whitespace, then consumes `expected`.
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.whitespace();
+        match self.chars.next() {
+            Some((_, c)) if c == expected => Ok(()),
+            Some((index, _)) => Err(format!("expected '{expected}' at byte {index}")),
+            None => Err("unexpected end of input".to_string()),
+        }
+    }
+
+    // Skips the whitespace JSON allows between tokens.
+    fn whitespace(&mut self) {
+        while self.chars.next_if(|&(_, c)| matches!(c, ' ' | '\t' | '\n' | '\r')).is_some() {}
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalars_pointers() {
+        let values = scalars(r#"{"level": "error", "ctx": {"codes": [404, true], "a/b": null}}"#).unwrap();
+
+        assert_eq!(values, vec![
+            ("/level".to_string(), "error".to_string()),
+            ("/ctx/codes/0".to_string(), "404".to_string()),
+            ("/ctx/codes/1".to_string(), "true".to_string()),
+            ("/ctx/a~1b".to_string(), "null".to_string()),
+        ])
+    }
+
+    #[test]
+    fn test_scalars_escapes() {
+        let values = scalars(r#"["tab\there", "\u00e9\ud83d\ude00"]"#).unwrap();
+
+        assert_eq!(values[0].1, "tab\there");
+        assert_eq!(values[1].1, "é😀")
+    }
+
+    #[test]
+    fn test_scalars_invalid() {
+        assert!(scalars(r#"{"a": 1"#).is_err());
+        assert!(scalars(r#"{"a": nope}"#).is_err());
+        assert!(scalars("[1] 2").is_err())
+    }
+}
@@ -17,13 +17,66 @@
 //! Inverting the search results. All lines without a match are returned.
 #![doc = include_str!("../examples/invert.md")]
 //!
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::{BufWriter, IsTerminal, LineWriter, Write};
+use std::cell::Cell;
+use std::fmt;
+use std::iter;
+use std::ops::{ControlFlow, Range};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::error::Error;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(feature = "cli")]
 use clap::Parser;
-use regex::bytes::Regex;
+#[cfg(feature = "cli")]
 use colored::*;
 
+/// Number of bytes searched between calls to a `Search` progress callback.
+pub const PROGRESS_INTERVAL: usize = 1 << 20;
+
+/// The exit status when a file could not be read.
+pub const ERROR_STATUS: i32 = 2;
+
+/// The exit status of a search stopped by Ctrl-C.
+pub const INTERRUPTED_STATUS: i32 = 128 + 2;
+
+#[cfg(feature = "async")]
+mod async_search;
+#[cfg(feature = "async")]
+pub use async_search::{search_path, search_reader};
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "cli")]
+pub mod config;
+mod matcher;
+use matcher::{engine, explain, literal_query, new_matcher, pattern_matchers, CaptureMatcher, Folding, LineBuffer, Matcher, Prepared};
+use memchr::{memchr_iter, memmem, memrchr};
+mod history;
+pub use history::{search_history, HistoryMatch};
+mod channel;
+pub use channel::{search_channel, OwnedMatch, SearchHandle};
+mod parallel;
+pub use parallel::{search_paths_parallel, PathErrors};
+mod incremental;
+pub use incremental::IncrementalSearch;
+mod json;
+pub use json::JsonMatch;
+mod heading;
+mod markdown;
+mod messages;
+mod posix;
+use messages::Message;
+use markdown::ScopeFilter;
+mod timestamp;
+mod cache;
+use timestamp::TimeWindow;
+
 
 /// A parser for command line input.
 /// 
@@ -33,25 +86,459 @@ use colored::*;
 /// # Options #
 #[doc = include_str!("../examples/help.md")]
 ///
-#[derive(Parser)]
-#[command(version, about = "A simple to use command line search tool, à la grep.", long_about = None)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Parser))]
+#[cfg_attr(feature = "cli", command(version, about = "A simple to use command line search tool, à la grep.", long_about = None, args_override_self = true))]
 pub struct CommandArgs {
     /// Search query
     query: String,
     /// File path
     path: PathBuf,
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     /// Ignores case whiles searching
     ignore_case: bool,
-    #[arg(short = 'v', long)]
+    #[cfg_attr(feature = "cli", arg(short = 'v', long))]
     /// Inverst search results
     invert_match: bool,
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     /// Matches exact words only
     word: bool,
-    #[arg(short, long)]
+    #[cfg_attr(feature = "cli", arg(short, long))]
     /// Matches exact lines only
     line: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Shows a progress bar while searching
+    progress: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "COLOR"))]
+    /// Color used to highlight matches [default: red]
+    match_color: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "NAME"))]
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    /// Uses the named profile from the config file
+    profile: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Shows the character column of the first match
+    column: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Shows the byte column of the first match
+    byte_column: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_enum, value_name = "FORM"))]
+    /// Applies Unicode normalization to the query and file before searching
+    normalize: Option<Normalization>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Ignores accents and other diacritics while searching
+    ignore_accents: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    /// Matches text within N edits of the query
+    fuzzy: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    /// Also matches lines containing PATTERN
+    any_of: Vec<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    /// Only matches lines that also contain PATTERN
+    all_of: Vec<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    /// Excludes lines containing PATTERN
+    none_of: Vec<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "A,B:N"))]
+    /// Only matches lines where A and B are at most N words apart
+    near: Option<Near>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "START:END"))]
+    /// Only searches lines START to END, either of which may be left out
+    line_range: Option<LineRange>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "TIME"))]
+    /// Only searches log lines stamped at or after TIME
+    since: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "TIME"))]
+    /// Only searches log lines stamped at or before TIME
+    until: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FORMAT"))]
+    /// Format of the timestamp at the start of each line [default: "%Y-%m-%d %H:%M:%S"]
+    time_format: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "FORMAT"))]
+    /// Makes file names and line numbers terminal hyperlinks, using "file", "vscode" or a template
+    hyperlink_format: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_enum, value_name = "FORMAT", conflicts_with_all = ["diff", "only_matching", "replace"]))]
+    /// Output format for the results [default: text]
+    format: Option<OutputFormat>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "TEMPLATE", conflicts_with_all = ["format", "diff", "only_matching", "replace"]))]
+    /// Writes each match using TEMPLATE, e.g. '{path}:{line}:{column} -> {text}'
+    format_template: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    /// Omits printed lines longer than N characters
+    max_columns: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, requires = "max_columns"))]
+    /// Prints N characters around the match of a long line instead of omitting it
+    max_columns_preview: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Strips leading whitespace from printed lines
+    trim: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    /// Expands tabs in printed lines to stops every N columns
+    tabs: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Prints control characters as they are instead of escaping them
+    no_escape: bool,
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["count_per_pattern", "files_with_all_matches", "json_input", "frequencies", "format", "format_template", "field", "diff", "hex_context", "filename_only"]))]
+    /// Prints the number of matches, counting each one on a line
+    count_matches: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Prints each distinct matching line only the first time it appears
+    dedupe: bool,
+    #[cfg_attr(feature = "cli", arg(long, requires = "dedupe"))]
+    /// Adds "(xN)" to deduplicated lines that appeared N times
+    dedupe_count: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Reports how long reading, matching and printing took on stderr
+    timing: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Searches every version of the file committed to git, printing commit:path:line
+    git_history: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Matches the query against the file path instead of its contents
+    filename_only: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_enum, value_name = "ACTION", default_value_t = Devices::Skip))]
+    /// What to do with FIFOs, sockets and device files
+    devices: Devices,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    /// Skips lines longer than N bytes instead of matching them
+    max_line_length: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, requires = "max_line_length"))]
+    /// Matches only the first N bytes of long lines instead of skipping them
+    truncate_long_lines: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Prints statistics about the search after the results
+    stats: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Flushes the output after every line instead of when the buffer fills
+    line_buffered: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SECS", value_parser = parse_seconds))]
+    /// Gives up on the whole search after SECS seconds
+    timeout: Option<Duration>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SECS", value_parser = parse_seconds))]
+    /// Skips a file that takes longer than SECS seconds to read and search
+    file_timeout: Option<Duration>,
+    #[cfg_attr(feature = "cli", arg(short = 's', long))]
+    /// Suppresses messages about unreadable and skipped files
+    no_messages: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Searches the file as raw bytes, allowing \xNN escapes in patterns
+    binary: bool,
+    #[cfg_attr(feature = "cli", arg(short = '0', long))]
+    /// Ends printed file names with a NUL byte instead of a newline
+    null: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    /// Stops searching after N matching lines
+    max_total: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["files_with_all_matches", "json_input", "frequencies", "format", "format_template", "field", "diff", "hex_context", "filename_only"]))]
+    /// Prints how many lines the query and each --any-of pattern matched
+    count_per_pattern: bool,
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["format", "format_template", "field", "diff", "hex_context", "filename_only"]))]
+    /// Prints each distinct matched text with its count, most common first
+    frequencies: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", conflicts_with_all = ["hex_context", "format_template", "format", "diff", "only_matching", "replace"]))]
+    /// Prints only the Nth field of each matching line, or the whole line for 0
+    field: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "DELIM", requires = "field"))]
+    /// Splits fields at DELIM instead of at whitespace
+    delimiter: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["frequencies", "format", "format_template", "field", "diff", "hex_context", "filename_only"]))]
+    /// Parses each line as JSON and matches the query against its values
+    json_input: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "POINTER", requires = "json_input"))]
+    /// Only matches values under POINTER, such as /request/url
+    json_path: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Prints the enclosing function or class above matches in source files
+    show_function: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "SCOPE"))]
+    /// Only searches one part of a Markdown document, showing section titles
+    md_scope: Option<MdScope>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", requires = "binary", conflicts_with_all = ["format_template", "format", "diff", "only_matching", "replace"]))]
+    /// Prints a hexdump of each match with N bytes around it
+    hex_context: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "LANG"))]
+    /// Language of messages and --stats output [default: from LANG]
+    lang: Option<Lang>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Prints how the query will be matched instead of searching
+    explain: bool,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "ignore_case"))]
+    /// Matches case exactly, even if -i or the config file says otherwise
+    case_sensitive: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_enum, value_name = "ENGINE", conflicts_with = "fuzzy"))]
+    /// How the query and other patterns are matched [default: literal]
+    engine: Option<Engine>,
+    #[cfg_attr(feature = "cli", arg(short = 'G', long, overrides_with = "extended_regexp", conflicts_with = "fuzzy"))]
+    /// Reads patterns as POSIX basic regular expressions
+    basic_regexp: bool,
+    #[cfg_attr(feature = "cli", arg(short = 'E', long, overrides_with = "basic_regexp", conflicts_with = "fuzzy"))]
+    /// Reads patterns as POSIX extended regular expressions
+    extended_regexp: bool,
+    #[cfg_attr(feature = "cli", arg(long, conflicts_with_all = ["json_input", "frequencies", "format", "format_template", "field", "diff", "hex_context", "filename_only"]))]
+    /// Prints the file path only if the query and every --any-of pattern match
+    files_with_all_matches: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "PATTERN"))]
+    /// Skips the file if its path matches PATTERN
+    invert_file_match: Option<String>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Removes ANSI escape codes, such as colors, from the file before searching
+    strip_ansi: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Turns off colors and locale-dependent messages, for comparing output
+    deterministic: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N"))]
+    /// Number printed for the first line, also used by --line-range [default: 1]
+    line_number_start: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Saves results to disk and reuses them when the same search is run on unchanged contents
+    cache: bool,
+    #[cfg_attr(feature = "cli", arg(long))]
+    /// Prints diagnostics to stderr, such as how the file was read and which engine matched it
+    verbose: bool,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "ignore_accents"))]
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    /// Turns off --ignore-accents, even if the config file turns it on
+    no_ignore_accents: bool,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "invert_match"))]
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    /// Turns off --invert-match, even if the config file turns it on
+    no_invert_match: bool,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "word"))]
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    /// Turns off --word, even if the config file turns it on
+    no_word: bool,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "line"))]
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    /// Turns off --line, even if the config file turns it on
+    no_line: bool,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "progress"))]
+    #[cfg_attr(not(feature = "cli"), allow(dead_code))]
+    /// Turns off --progress, even if the config file turns it on
+    no_progress: bool,
+    #[cfg_attr(feature = "cli", arg(short = 'o', long, conflicts_with = "invert_match"))]
+    /// Prints only the matched parts of each line, one per line
+    only_matching: bool,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "N", requires = "only_matching", conflicts_with_all = ["replace", "fuzzy"]))]
+    /// Prints only capture group N of each match with -o, given by number or name
+    group: Option<String>,
+    #[cfg_attr(feature = "cli", arg(short = 'r', long, value_name = "TEXT", conflicts_with_all = ["invert_match", "fuzzy"]))]
+    /// Replaces each match with TEXT, where $1 or $name is a capture group
+    replace: Option<String>,
+    #[cfg_attr(feature = "cli", arg(short = 'A', long, value_name = "NUM"))]
+    /// Prints NUM lines after each match
+    after_context: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(short = 'B', long, value_name = "NUM"))]
+    /// Prints NUM lines before each match
+    before_context: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(short = 'C', long, value_name = "NUM"))]
+    /// Prints NUM lines before and after each match, unless -A or -B says otherwise
+    context: Option<usize>,
+    #[cfg_attr(feature = "cli", arg(long, value_name = "STR", default_value = "--"))]
+    /// Printed between groups of lines that aren't next to each other
+    context_separator: String,
+    #[cfg_attr(feature = "cli", arg(long, overrides_with = "context_separator"))]
+    /// Prints nothing between groups of context lines
+    no_context_separator: bool,
+    #[cfg_attr(feature = "cli", arg(long, requires = "replace", conflicts_with = "only_matching"))]
+    /// Prints the changes --replace would make to the file as a unified diff
+    diff: bool,
+}
+
+/// A Unicode normalization form applied before matching.
+/// 
+/// Normalizing both the query and the contents lets text match even
+/// when it was written with different, but equivalent, code points.
+/// With `Nfc` a composed "é" matches "e" followed by a combining accent,
+/// and `Nfkc` also matches compatibility forms such as "ﬁ" with "fi".
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Normalization {
+    /// Canonical composition
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Nfc,
+    /// Compatibility composition
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Nfkc,
+}
+
+/// Two terms that must occur close to each other on a line.
+/// 
+/// Parsed from `A,B:N`, where `A` and `B` are the terms and `N` is the
+/// largest number of words between the start of one and the start of
+/// the other. Terms follow the same rules as the query, so with `-w`
+/// they must be whole words.
+/// 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Near {
+    /// The first term.
+    pub first: String,
+    /// The second term.
+    pub second: String,
+    /// The largest distance between the terms, in words.
+    pub distance: usize,
+}
+
+/// How search results are written.
+/// 
+/// `Csv` and `Tsv` write a header row followed by one row per match with
+/// the columns `path`, `line`, `column`, `match` and `line_text`.
+/// `Sarif` writes a single SARIF log where the query is the rule and
+/// each match is a result. `Github` writes a `::warning` workflow
+/// command per match, which GitHub Actions shows inline on pull requests.
+/// `Emacs` writes one plain `file:line:column: text` line per match, the
+/// shape parsed by Emacs' grep and compilation modes.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum OutputFormat {
+    /// Human readable lines
+    #[default]
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Text,
+    /// Comma separated values
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Csv,
+    /// Tab separated values
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Tsv,
+    /// A SARIF 2.1.0 log for code scanning tools
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Sarif,
+    /// GitHub Actions warning annotations
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Github,
+    /// `file:line:column: text` lines for Emacs and other editors
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Emacs,
+}
+
+/// How the query and other patterns are matched.
+/// 
+/// `Literal` matches the text as it is written. `Regex` treats it as a
+/// regular expression for the regex crate, and falls back to `Fancy`
+/// for patterns that need lookaround or backreferences when the `fancy`
+/// feature is enabled. `Auto` uses `Literal` when no pattern contains
+/// regex syntax and `Regex` otherwise. `Pcre2` uses the PCRE2 library,
+/// like `grep -P`, and needs the `pcre2` feature. Patterns for `--near`
+/// are always literal.
+/// 
+/// On the command line `default` is another name for `literal`, the
+/// engine used when `--engine` is left out.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Engine {
+    /// The cheapest engine that handles the patterns
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Auto,
+    /// Plain text
+    #[default]
+    #[cfg_attr(feature = "cli", value(help = None::<&str>, alias = "default"))]
+    Literal,
+    /// Regular expressions
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Regex,
+    /// Regular expressions with lookaround and backreferences
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Fancy,
+    /// Perl-compatible regular expressions
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Pcre2,
+}
+
+/// The part of a Markdown document searched with `--md-scope`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum MdScope {
+    /// `#` headings
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Headings,
+    /// Fenced code blocks, including the fences
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Code,
+    /// Every other line
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Prose,
+}
+
+/// The language of messages and `--stats` output.
+/// 
+/// Without `--lang` it is picked from the locale with `Lang::from_env`.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Lang {
+    /// English
+    #[default]
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    En,
+    /// German
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    De,
+    /// Spanish
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Es,
+    /// French
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Fr,
+}
+
+/// What to do when the path is a FIFO, socket or device file.
+/// 
+/// Reading one of these can block forever or never reach the end, as
+/// with a FIFO nobody writes to or `/dev/zero`, so they are skipped
+/// unless asked for.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum Devices {
+    /// Reads the file like any other
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Read,
+    /// Refuses to read the file
+    #[default]
+    #[cfg_attr(feature = "cli", value(help = None::<&str>))]
+    Skip,
+}
+
+/// An inclusive range of line numbers to search.
+/// 
+/// Parsed from `START:END`. Leaving out `START` searches from the first
+/// line and leaving out `END` searches to the last one, so `100:` and
+/// `:500` are both valid.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineRange {
+    /// The first line to search.
+    pub start: Option<usize>,
+    /// The last line to search.
+    pub end: Option<usize>,
+}
+
+/// A query compiled from `CommandArgs`, ready to search any contents.
+/// 
+/// Compiling prepares the query and builds its matcher once, so a
+/// `CompiledQuery` can be reused across many searches with
+/// `Search::find_compiled`.
+/// 
+pub struct CompiledQuery<'q> {
+    args: &'q CommandArgs,
+    folding: Folding,
+    matcher: Box<dyn Matcher>,
+    window: Option<TimeWindow>,
+    scope: Option<ScopeFilter>,
+}
+
+// The state of one pass of a `CompiledQuery` over a sequence of lines:
+// the filters that depend on earlier lines, and a buffer for preparing
+// each line. Shared by every way of feeding lines to a search, so they
+// all select the same lines.
+pub(crate) struct LineSearch {
+    window: Option<TimeWindow>,
+    scope: Option<ScopeFilter>,
+    buffer: LineBuffer,
 }
 
 /// Stores the results of the search and a reference to the contents.
@@ -62,6 +549,160 @@ pub struct CommandArgs {
 pub struct Search<'a> {
     contents: &'a str,
     results: Vec<(usize, &'a str)>,
+    matches: Vec<Match<'a>>,
+    cancel: Option<CancellationToken>,
+    progress: Option<ProgressCallback<'a>>,
+    stats: Cell<Stats>,
+}
+
+/// The line number and text of each line selected by a search.
+pub type Results<'a> = Vec<(usize, &'a str)>;
+
+/// A callback receiving progress updates from a `Search`.
+pub type ProgressCallback<'a> = Box<dyn Fn(&Progress) + 'a>;
+
+/// A snapshot of how far a search has progressed.
+/// 
+/// Passed to the callback set with `Search::set_progress`.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Number of files found so far.
+    pub files_discovered: usize,
+    /// Number of files that have been completely searched.
+    pub files_searched: usize,
+    /// Number of bytes of contents searched so far.
+    pub bytes_scanned: usize,
+}
+
+/// Counts collected by the most recent search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// Number of lines checked against the query.
+    pub lines_searched: usize,
+    /// Number of lines selected by the search.
+    pub lines_matched: usize,
+    /// Number of lines skipped for being longer than `--max-line-length`.
+    pub lines_skipped: usize,
+    /// Number of long lines where only the start was matched.
+    pub lines_truncated: usize,
+    /// Number of bytes of lines checked against the query.
+    pub bytes_searched: usize,
+}
+
+/// Counts and timing for a search over one or more files.
+/// 
+/// Returned by `search_paths_parallel` and `search_channel`, so a host
+/// application can show its own summary instead of `--stats`.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SearchStats {
+    /// Number of files read and searched.
+    pub files_searched: usize,
+    /// Number of files that could not be read.
+    pub files_skipped: usize,
+    /// Line and byte counts added up over every file searched.
+    pub lines: Stats,
+    /// Time from the start of the search until the last file was done.
+    pub elapsed: Duration,
+}
+
+/// A shared flag used to stop a running search from another thread.
+/// 
+/// Clones of a token share the same flag, so a host application (or a
+/// Ctrl-C handler) can keep one clone and hand another to `Search`.
+/// 
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+/// The error returned when a search is stopped by a `CancellationToken`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+/// The error returned by `run` when a file could not be read.
+/// 
+/// The reason has already been printed to stderr, unless `no_messages`
+/// is set.
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReadErrors;
+
+/// A single line selected by a search.
+/// 
+/// Passed to the closure given to `Search::find_with` as each
+/// result is found.
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Match<'a> {
+    /// Line number within the contents.
+    pub number: usize,
+    /// The full text of the line.
+    pub line: &'a str,
+    /// Location of the first match in `line`.
+    /// 
+    /// This is `None` for lines selected by `invert_match`.
+    pub span: Option<Span>,
+}
+
+/// A line of the contents, with its 0-based line number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Line<'a> {
+    /// Line number within the contents.
+    pub number: usize,
+    /// The full text of the line.
+    pub text: &'a str,
+}
+
+/// The lines around a `Match`, as returned by `Search::context`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Context<'a> {
+    /// Lines before the match, nearest last.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub before: Vec<Line<'a>>,
+    /// Lines after the match, nearest first.
+    #[cfg_attr(feature = "serde", serde(borrow))]
+    pub after: Vec<Line<'a>>,
+}
+
+/// Where `Search::find_page` should pick up the search.
+/// 
+/// `Cursor::default()` starts at the first line. A cursor only fits the
+/// contents and options of the search that returned it.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cursor {
+    number: usize,
+    offset: usize,
+}
+
+/// One page of results, as returned by `Search::find_page`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page<'a> {
+    /// The matches on this page, in order.
+    pub matches: Vec<Match<'a>>,
+    /// Where the next page starts, or `None` if this is the last page.
+    pub next: Option<Cursor>,
+}
+
+/// The byte range of a match within its line.
+/// 
+/// Both ends always fall on character boundaries of the line, even when
+/// case folding changed the length of the text that was matched, so a
+/// `Span` can be used to slice the line directly.
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    /// Byte offset of the start of the match.
+    pub start: usize,
+    /// Byte offset just past the end of the match.
+    pub end: usize,
 }
 
 /// Defines methods expected to run on `CommandArgs`.
@@ -108,196 +749,2738 @@ impl CommandArgs {
             ignore_case,
             invert_match,
             word,
-            line 
+            line,
+            progress: false,
+            match_color: None,
+            profile: None,
+            column: false,
+            byte_column: false,
+            normalize: None,
+            ignore_accents: false,
+            fuzzy: None,
+            any_of: Vec::new(),
+            all_of: Vec::new(),
+            none_of: Vec::new(),
+            near: None,
+            line_range: None,
+            since: None,
+            until: None,
+            time_format: None,
+            hyperlink_format: None,
+            format: None,
+            format_template: None,
+            max_columns: None,
+            max_columns_preview: false,
+            trim: false,
+            tabs: None,
+            no_escape: false,
+            count_matches: false,
+            dedupe: false,
+            dedupe_count: false,
+            timing: false,
+            git_history: false,
+            filename_only: false,
+            devices: Devices::Skip,
+            max_line_length: None,
+            truncate_long_lines: false,
+            stats: false,
+            line_buffered: false,
+            timeout: None,
+            file_timeout: None,
+            no_messages: false,
+            binary: false,
+            null: false,
+            max_total: None,
+            count_per_pattern: false,
+            frequencies: false,
+            field: None,
+            delimiter: None,
+            json_input: false,
+            json_path: None,
+            show_function: false,
+            md_scope: None,
+            hex_context: None,
+            lang: None,
+            explain: false,
+            case_sensitive: false,
+            engine: None,
+            basic_regexp: false,
+            extended_regexp: false,
+            files_with_all_matches: false,
+            invert_file_match: None,
+            strip_ansi: false,
+            deterministic: false,
+            line_number_start: None,
+            cache: false,
+            verbose: false,
+            no_ignore_accents: false,
+            no_invert_match: false,
+            no_word: false,
+            no_line: false,
+            no_progress: false,
+            only_matching: false,
+            group: None,
+            replace: None,
+            after_context: None,
+            before_context: None,
+            context: None,
+            context_separator: "--".to_string(),
+            no_context_separator: false,
+            diff: false,
         }
     }
-}
 
-impl RunArgs for CommandArgs {
-    /// Executes the search process given the command line arguments.
+    /// Sets the Unicode normalization applied before matching.
     /// 
-    /// Reads contents of the specified file and generates a new 
-    /// `Search` struct to store the results. Once completed, the results
-    /// are written to the terminal.
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{search_str, CommandArgs, Normalization};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("caf\u{e9}".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_normalize(Some(Normalization::Nfc));
     /// 
-    /// # Returns
-    /// Returns () if successful.
+    /// let results = search_str("cafe\u{301}", &args).unwrap();
+    /// assert_eq!(results.len(), 1);
+    /// ```
     /// 
-    fn run(&self) -> Result<(), Box<dyn Error>> {
-        let contents = self.read()?;
-        let mut search = Search::new(&contents);
-        search.find(&self)?;
-        search.write(&self, &mut std::io::stdout())?;
-        Ok(())
+    pub fn with_normalize(mut self, form: Option<Normalization>) -> CommandArgs {
+        self.normalize = form;
+        self
     }
 
-    /// Reads and stores the contents of a file.
+    /// Sets whether accents and other diacritics are ignored.
     /// 
-    /// # Returns
-    /// Returns the contents of a file as a `String`.
+    /// Accents are removed from both the query and the contents before
+    /// matching, so "resume" matches "résumé" and the reverse.
     /// 
-    fn read(&self) -> Result<String, Box<dyn Error>> {
-        let contents = fs::read_to_string(&self.path)?;
-        Ok(contents)
+    pub fn with_ignore_accents(mut self, ignore_accents: bool) -> CommandArgs {
+        self.ignore_accents = ignore_accents;
+        self
     }
-}
 
+    /// Matches text within `max_edits` insertions, deletions or
+    /// substitutions of the query, or only exact text if `None`.
+    pub fn with_fuzzy(mut self, max_edits: Option<usize>) -> CommandArgs {
+        self.fuzzy = max_edits;
+        self
+    }
 
-/// Defines methods expected to run on `Search`.
-pub trait IsSearch {
-    /// Searchs for the query in the file contents.
-    fn find(&mut self, args: &CommandArgs) -> Result<(), Box<dyn Error>>;
-}
-
-impl<'a> Search<'a> {
-    /// Creates a new `Search`.
+    /// Sets extra patterns that match lines in place of the query.
     /// 
-    /// # Returns
-    /// Returns a `Search` containing a reference to `contents` 
-    /// and an empty `results` vector.
+    /// A line matches if it contains the query or any of `patterns`.
+    /// Each pattern is highlighted in its own color, and SARIF output
+    /// reports the pattern that matched as the rule.
+    /// 
+    pub fn with_any_of(mut self, patterns: Vec<String>) -> CommandArgs {
+        self.any_of = patterns;
+        self
+    }
+
+    /// Sets patterns that every matching line must also contain.
     /// 
     /// # Example
     /// ```
-    /// # use crate::grepr::Search;
-    /// let some_text = "This is a test.\n With two lines.".to_string();
+    /// # use crate::grepr::{search_str, CommandArgs};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("error".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_all_of(vec!["timeout".to_string()])
+    ///     .with_none_of(vec!["retry".to_string()]);
     /// 
-    /// let new_search = Search::new(&some_text);
+    /// let results = search_str("error: timeout\nerror: timeout, retry\nerror: refused", &args).unwrap();
+    /// assert_eq!(results, vec![(0, "error: timeout")]);
     /// ```
     /// 
-    pub fn new(contents: &'a str) -> Search<'a> {
-        Search { contents, results: Vec::new() }
+    pub fn with_all_of(mut self, patterns: Vec<String>) -> CommandArgs {
+        self.all_of = patterns;
+        self
     }
 
-    /// Writes the search results to the command line.
-    fn write(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
-        writeln!(writer, "{}", args.path.display())?;
-        for (number, line) in &self.results {
-            let colored_line = line.replace(&args.query, &args.query.red().bold().to_string());
-            writeln!(writer, "{number}: {}", colored_line)?;
-        }
-        Ok(())
+    /// Sets patterns that exclude any line containing them.
+    pub fn with_none_of(mut self, patterns: Vec<String>) -> CommandArgs {
+        self.none_of = patterns;
+        self
     }
 
-    /// Returns the raw results vector from `Search`.
-    pub fn get_results(&self) -> &Vec<(usize, &'a str)> {
-        &self.results
+    /// Sets two terms that must occur within a few words of each other.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{search_str, CommandArgs};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_near(Some("error,database:2".parse().unwrap()));
+    /// 
+    /// let results = search_str("error in database\nerror, see the logs of the database", &args).unwrap();
+    /// assert_eq!(results, vec![(0, "error in database")]);
+    /// ```
+    /// 
+    pub fn with_near(mut self, near: Option<Near>) -> CommandArgs {
+        self.near = near;
+        self
     }
-}
 
-impl<'a> IsSearch for Search<'a> {
-    /// Searchs the file path for the query string.
-    fn find(&mut self, args: &CommandArgs) -> Result<(), Box<dyn Error>> {
-        let query = prep_string(&args.query.to_string(), args.ignore_case);
-        let word_regex = Regex::new(r"\W+").unwrap();
-        for (number, line) in self.contents.lines().enumerate() {
-            let search_line = prep_string(line, args.ignore_case);
+    /// Restricts the search to a range of line numbers.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{search_str, CommandArgs};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_line_range(Some("1:".parse().unwrap()));
+    /// 
+    /// let results = search_str("this is a test.\nthis is another test!", &args).unwrap();
+    /// assert_eq!(results, vec![(1, "this is another test!")]);
+    /// ```
+    /// 
+    pub fn with_line_range(mut self, range: Option<LineRange>) -> CommandArgs {
+        self.line_range = range;
+        self
+    }
 
-            let line_match = args.line && search_line == query;
-            let word_match = !args.line && args.word && word_regex.split(&search_line).any(|word| word == query);
-            let partial_match = !args.line && !args.word && search_line.windows(query.len()).any(|window| window == query);
+    /// Restricts the search to log lines stamped inside a time window.
+    /// 
+    /// Each line is checked for a timestamp at its start, and `since` and
+    /// `until` must be written in the same format. Lines without a
+    /// timestamp belong to the last line that had one.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{search_str, CommandArgs};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("error".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_time_window(Some("2024-01-05 10:00:00".to_string()), None);
+    /// 
+    /// let contents = "2024-01-05 09:00:00 error: old\n2024-01-05 10:30:00 error: new";
+    /// let results = search_str(contents, &args).unwrap();
+    /// assert_eq!(results, vec![(1, "2024-01-05 10:30:00 error: new")]);
+    /// ```
+    /// 
+    pub fn with_time_window(mut self, since: Option<String>, until: Option<String>) -> CommandArgs {
+        self.since = since;
+        self.until = until;
+        self
+    }
 
-            let match_found: bool = line_match || word_match || partial_match;
-            
-            if match_found && !args.invert_match || !match_found && args.invert_match {
-                self.results.push((number, line));
-            }
-        }
+    /// Sets the format of the timestamp at the start of each line.
+    /// 
+    /// The format may use `%Y`, `%m`, `%d`, `%H`, `%M` and `%S`, along
+    /// with literal characters. If `None`, `%Y-%m-%d %H:%M:%S` is used.
+    /// 
+    pub fn with_time_format(mut self, format: Option<String>) -> CommandArgs {
+        self.time_format = format;
+        self
+    }
 
-        Ok(())
+    /// Sets the format of the terminal hyperlinks written for results.
+    /// 
+    /// `"file"` links to `file://` URLs and `"vscode"` opens results in
+    /// VS Code. Any other value is a template where `{path}`, `{line}`
+    /// and `{column}` are replaced by the location of a result. No links
+    /// are written if `None`.
+    /// 
+    pub fn with_hyperlink_format(mut self, format: Option<String>) -> CommandArgs {
+        self.hyperlink_format = format;
+        self
     }
-}
 
+    /// Sets how search results are written.
+    pub fn with_format(mut self, format: OutputFormat) -> CommandArgs {
+        self.format = Some(format);
+        self
+    }
 
-// helper methods
+    /// Sets a template used to write each match, in place of `format`.
+    /// 
+    /// The template may use `{path}`, `{line}`, `{column}`,
+    /// `{byte_column}`, `{match}` and `{text}`, the whole line. Literal
+    /// braces are written as `{{` and `}}`.
+    /// 
+    pub fn with_format_template(mut self, template: Option<String>) -> CommandArgs {
+        self.format_template = template;
+        self
+    }
 
-// Prepares a string for saerch.
-// The string is converted to lowercase if lower == true.
-// Either way, the string is converted to bytes ahead of the search.
-fn prep_string(str: &str, lower: bool) -> Vec<u8> {
-    if lower {
-        str.to_lowercase().into_bytes()
-    } else {
-        str.to_string().into_bytes()
+    /// Sets the longest line, in characters, that is printed in full.
+    /// 
+    /// Longer lines are replaced by a note, or by a preview around the
+    /// match if `preview` is true.
+    /// 
+    pub fn with_max_columns(mut self, max_columns: Option<usize>, preview: bool) -> CommandArgs {
+        self.max_columns = max_columns;
+        self.max_columns_preview = preview;
+        self
     }
-}
 
+    /// Sets whether leading whitespace is stripped from printed lines.
+    pub fn with_trim(mut self, trim: bool) -> CommandArgs {
+        self.trim = trim;
+        self
+    }
 
+    /// Sets the tab stop width used to expand tabs in printed lines.
+    /// 
+    /// Tabs are printed as they are if `None`.
+    /// 
+    pub fn with_tabs(mut self, width: Option<usize>) -> CommandArgs {
+        self.tabs = width;
+        self
+    }
 
+    /// Sets whether control characters are printed as they are.
+    /// 
+    /// By default, control characters other than tab are printed as
+    /// escapes like `\x1b` so a file can't change the terminal's state.
+    /// 
+    pub fn with_no_escape(mut self, no_escape: bool) -> CommandArgs {
+        self.no_escape = no_escape;
+        self
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Sets whether only the number of matches is printed.
+    pub fn with_count_matches(mut self, count_matches: bool) -> CommandArgs {
+        self.count_matches = count_matches;
+        self
+    }
 
-    #[test]
-    fn test_search_line_case_noinvert_good() {
-        let query = "this is a test.".to_string();
-        let path = PathBuf::new();
-        let contents = "this is a test.\nthis is another test!";
-        let ignore_case = false;
-        let invert_match = false;
-        let word = false;
-        let line = true;
+    /// Sets whether repeated identical lines are printed only once.
+    /// 
+    /// If `count` is true, lines that were repeated end with the number
+    /// of times they appeared, such as `(x3)`.
+    /// 
+    pub fn with_dedupe(mut self, dedupe: bool, count: bool) -> CommandArgs {
+        self.dedupe = dedupe;
+        self.dedupe_count = count;
+        self
+    }
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+    /// Sets whether `run` reports the time taken by each stage on stderr.
+    pub fn with_timing(mut self, timing: bool) -> CommandArgs {
+        self.timing = timing;
+        self
+    }
 
-        let mut search = Search::new(&contents);
-        let _ = search.find(&args);
+    /// Sets whether `run` searches the file's git history instead of the
+    /// file on disk.
+    pub fn with_git_history(mut self, git_history: bool) -> CommandArgs {
+        self.git_history = git_history;
+        self
+    }
 
-        assert_eq!(search.results[0].1, "this is a test.")
+    /// Sets whether `run` matches the query against the file path
+    /// instead of reading the file.
+    pub fn with_filename_only(mut self, filename_only: bool) -> CommandArgs {
+        self.filename_only = filename_only;
+        self
     }
 
+    /// Sets what `read` does when the path is a FIFO, socket or device.
+    pub fn with_devices(mut self, devices: Devices) -> CommandArgs {
+        self.devices = devices;
+        self
+    }
 
+    /// Sets the longest line, in bytes, that is matched in full.
+    /// 
+    /// Longer lines are skipped, or only their first `max` bytes are
+    /// matched if `truncate` is true. Either way, they are counted in
+    /// `Stats::lines_skipped` or `Stats::lines_truncated`.
+    /// 
+    pub fn with_max_line_length(mut self, max: Option<usize>, truncate: bool) -> CommandArgs {
+        self.max_line_length = max;
+        self.truncate_long_lines = truncate;
+        self
+    }
 
-    #[test]
-    fn test_search_line_case_noinvert_bad() {
-        let query = "this is a test".to_string();
-        let path = PathBuf::new();
-        let contents = "this is a test.\nthis is another test!";
-        let ignore_case = false;
-        let invert_match = false;
-        let word = false;
-        let line = true;
+    /// Sets whether `run` prints the search's `Stats` after the results.
+    pub fn with_stats(mut self, stats: bool) -> CommandArgs {
+        self.stats = stats;
+        self
+    }
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+    /// Sets whether the results are flushed after every line.
+    /// 
+    /// Output is otherwise buffered, so a downstream reader may not see
+    /// a result until more have been written.
+    /// 
+    pub fn with_line_buffered(mut self, line_buffered: bool) -> CommandArgs {
+        self.line_buffered = line_buffered;
+        self
+    }
 
-        let mut search = Search::new(&contents);
-        let _ = search.find(&args);
+    /// Sets how long `run` may take overall and for each file.
+    /// 
+    /// A file that runs out of time is reported as skipped and none of
+    /// its results are printed.
+    /// 
+    pub fn with_timeout(mut self, timeout: Option<Duration>, file_timeout: Option<Duration>) -> CommandArgs {
+        self.timeout = timeout;
+        self.file_timeout = file_timeout;
+        self
+    }
 
-        assert_eq!(search.results.len(), 0)
+    /// Sets whether `run` stays quiet about files it could not read or
+    /// skipped.
+    /// 
+    /// Such files still make `run` return a `ReadErrors` error.
+    /// 
+    pub fn with_no_messages(mut self, no_messages: bool) -> CommandArgs {
+        self.no_messages = no_messages;
+        self
     }
 
-    #[test]
-    fn test_search_line_nocase_noinvert_good() {
-        let query = "THIS is a test.".to_string();
-        let path = PathBuf::new();
-        let contents = "this is a test.\nthis is another test!";
-        let ignore_case = true;
-        let invert_match = false;
-        let word = false;
-        let line = true;
+    /// Sets whether `read` and `run` treat the file as raw bytes.
+    /// 
+    /// Each byte is read as the character with the same value, so files
+    /// that aren't valid UTF-8 can be searched. `run` also decodes `\xNN`
+    /// escapes in the patterns, and prints bytes above `\x7f` escaped.
+    /// 
+    pub fn with_binary(mut self, binary: bool) -> CommandArgs {
+        self.binary = binary;
+        self
+    }
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+    /// Sets whether printed file names end with a NUL byte instead of a
+    /// newline, for piping into `xargs -0`.
+    pub fn with_null(mut self, null: bool) -> CommandArgs {
+        self.null = null;
+        self
+    }
+
+    /// Sets the most results `find` collects before it stops searching.
+    pub fn with_max_total(mut self, max_total: Option<usize>) -> CommandArgs {
+        self.max_total = max_total;
+        self
+    }
+
+    /// Sets whether only the number of lines matched by each pattern is
+    /// printed.
+    pub fn with_count_per_pattern(mut self, count_per_pattern: bool) -> CommandArgs {
+        self.count_per_pattern = count_per_pattern;
+        self
+    }
+
+    /// Sets whether only the count of each distinct matched text is
+    /// printed.
+    pub fn with_frequencies(mut self, frequencies: bool) -> CommandArgs {
+        self.frequencies = frequencies;
+        self
+    }
+
+    /// Sets the field of each matching line that is printed.
+    /// 
+    /// Fields are numbered from 1, like in awk, and 0 is the whole line.
+    /// They are separated by runs of whitespace, or by each `delimiter`
+    /// if one is given. A line without the field prints as empty.
+    /// 
+    pub fn with_field(mut self, field: Option<usize>, delimiter: Option<String>) -> CommandArgs {
+        self.field = field;
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// Sets whether `run` searches the values of JSON documents, one per
+    /// line, instead of the text of each line.
+    /// 
+    /// With `path`, only values at or below that JSON pointer are
+    /// matched.
+    /// 
+    pub fn with_json_input(mut self, json_input: bool, path: Option<String>) -> CommandArgs {
+        self.json_input = json_input;
+        self.json_path = path;
+        self
+    }
+
+    /// Sets whether the text output shows the function or class each
+    /// result is in.
+    /// 
+    /// The heading is printed as `number= text` above the first result
+    /// under it. Headings are found with simple per-language patterns,
+    /// picked by the file extension. Rust, Python, JavaScript,
+    /// TypeScript, Go, Ruby, Java, C# and C/C++ are recognized.
+    /// 
+    pub fn with_show_function(mut self, show_function: bool) -> CommandArgs {
+        self.show_function = show_function;
+        self
+    }
+
+    /// Restricts the search to the headings, code blocks or prose of a
+    /// Markdown document.
+    /// 
+    /// The text output also shows the section title above results, as
+    /// with `with_show_function`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, IsSearch, MdScope, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("cargo".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_md_scope(Some(MdScope::Code));
+    /// let mut search = Search::new("Install with cargo:\n```\ncargo install grepr\n```");
+    /// let _ = search.find(&args);
+    /// 
+    /// assert_eq!(search.get_results(), &vec![(2, "cargo install grepr")]);
+    /// ```
+    /// 
+    pub fn with_md_scope(mut self, md_scope: Option<MdScope>) -> CommandArgs {
+        self.md_scope = md_scope;
+        self
+    }
+
+    /// Sets how many bytes around each match are shown in a hexdump.
+    /// 
+    /// Only used with `binary`, where results are printed as the match
+    /// offset followed by `hexdump -C` style rows instead of lines.
+    /// 
+    pub fn with_hex_context(mut self, hex_context: Option<usize>) -> CommandArgs {
+        self.hex_context = hex_context;
+        self
+    }
+
+    /// Sets the language of messages and `--stats` output.
+    /// 
+    /// With `None` the language is picked from the locale.
+    /// 
+    pub fn with_lang(mut self, lang: Option<Lang>) -> CommandArgs {
+        self.lang = lang;
+        self
+    }
+
+    /// Sets whether to describe how the query will be matched instead
+    /// of searching.
+    /// 
+    /// The description covers case and accent folding, the compiled
+    /// query, whether whole words or lines must match, and whether the
+    /// contents are scanned at once or line by line.
+    /// 
+    pub fn with_explain(mut self, explain: bool) -> CommandArgs {
+        self.explain = explain;
+        self
+    }
+
+    /// Sets whether case must match exactly.
+    /// 
+    /// This turns off `ignore_case`, and keeps the `ignore-case` config
+    /// file option from turning it back on.
+    /// 
+    pub fn with_case_sensitive(mut self, case_sensitive: bool) -> CommandArgs {
+        self.case_sensitive = case_sensitive;
+        self.ignore_case &= !case_sensitive;
+        self
+    }
+
+    /// Sets how the query and other patterns are matched.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{search_str, CommandArgs, Engine};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new(r"v\d+\.\d+".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_engine(Engine::Regex);
+    /// 
+    /// assert_eq!(search_str("grepr v0.1\nno version", &args).unwrap(), vec![(0, "grepr v0.1")]);
+    /// ```
+    /// 
+    pub fn with_engine(mut self, engine: Engine) -> CommandArgs {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Sets whether patterns are POSIX basic regular expressions, as
+    /// with `grep -G`.
+    /// 
+    /// Patterns are translated for the regex crate, so `\(a\)\{2\}`
+    /// becomes `(a){2}`. Unless another engine is set, this uses
+    /// `Engine::Regex`.
+    /// 
+    pub fn with_basic_regexp(mut self, basic_regexp: bool) -> CommandArgs {
+        self.basic_regexp = basic_regexp;
+        self
+    }
+
+    /// Sets whether patterns are POSIX extended regular expressions, as
+    /// with `grep -E`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{search_str, CommandArgs};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("[[:digit:]]+ (errors|warnings)".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_extended_regexp(true);
+    /// 
+    /// assert_eq!(search_str("3 warnings\nno errors", &args).unwrap(), vec![(0, "3 warnings")]);
+    /// ```
+    /// 
+    pub fn with_extended_regexp(mut self, extended_regexp: bool) -> CommandArgs {
+        self.extended_regexp = extended_regexp;
+        self
+    }
+
+    /// Sets whether only the file path is printed, and only if the query
+    /// and every `any_of` pattern each match somewhere in the file.
+    pub fn with_files_with_all_matches(mut self, files_with_all_matches: bool) -> CommandArgs {
+        self.files_with_all_matches = files_with_all_matches;
+        self
+    }
+
+    /// Sets a pattern that skips the file when its path matches.
+    /// 
+    /// The pattern is matched against the path like the query, with the
+    /// same case, word and engine options, but without `invert_match` or
+    /// any extra patterns.
+    /// 
+    pub fn with_invert_file_match(mut self, pattern: Option<String>) -> CommandArgs {
+        self.invert_file_match = pattern;
+        self
+    }
+
+    /// Sets whether ANSI escape codes are removed from the file as it is
+    /// read.
+    /// 
+    /// This lets color codes in logs sit inside text the query should
+    /// match. The codes are gone from the printed results too.
+    /// 
+    pub fn with_strip_ansi(mut self, strip_ansi: bool) -> CommandArgs {
+        self.strip_ansi = strip_ansi;
+        self
+    }
+
+    /// Sets whether output is kept the same from one run to the next.
+    /// 
+    /// Colors are turned off and messages are in English unless `lang`
+    /// is set, so the output doesn't depend on the terminal or locale.
+    /// Searches already run on one thread and results come in file order.
+    /// 
+    pub fn with_deterministic(mut self, deterministic: bool) -> CommandArgs {
+        self.deterministic = deterministic;
+        self
+    }
+
+    /// Sets the number printed for the first line of the file.
+    /// 
+    /// Results keep 0-based line numbers, but printed output adds this,
+    /// so by default the first line is line 1. A chunk cut from the
+    /// middle of a file can start from its real line number instead.
+    /// The `--line-range` given on the command line counts the same way.
+    /// 
+    pub fn with_line_number_start(mut self, line_number_start: usize) -> CommandArgs {
+        self.line_number_start = Some(line_number_start);
+        self
+    }
+
+    /// Sets whether `run` uses the on-disk cache of search results.
+    /// 
+    /// If so, `run` saves the matches of a plain search, and reuses them
+    /// while the file's contents and every option stay the same.
+    /// 
+    pub fn with_cache(mut self, cache: bool) -> CommandArgs {
+        self.cache = cache;
+        self
+    }
+
+    /// Sets whether `run` prints diagnostics about the file to stderr.
+    /// 
+    /// These cover how the file was read, the engine used to match it
+    /// and whether cached results were reused. They are printed even
+    /// with `no_messages`, which only silences errors.
+    /// 
+    pub fn with_verbose(mut self, verbose: bool) -> CommandArgs {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Sets whether `write` prints only the matched parts of each line.
+    /// 
+    /// Each part is printed on its own line, after the line number.
+    /// Only the text output is affected.
+    /// 
+    pub fn with_only_matching(mut self, only_matching: bool) -> CommandArgs {
+        self.only_matching = only_matching;
+        self
+    }
+
+    /// Sets the capture group printed in place of each match with
+    /// `only_matching`, given by number or name.
+    /// 
+    /// Group 0 is the whole match. A match where the group took no part
+    /// prints nothing.
+    /// 
+    pub fn with_group(mut self, group: Option<String>) -> CommandArgs {
+        self.group = group;
+        self
+    }
+
+    /// Sets the text each match of the query is replaced with in the
+    /// output.
+    /// 
+    /// `$1` and `$name`, or `${1}` and `${name}`, stand for the text of a
+    /// capture group, and `$$` for a `$`. With `only_matching`, only the
+    /// replacements are printed. The file itself is never changed.
+    /// 
+    pub fn with_replace(mut self, replace: Option<String>) -> CommandArgs {
+        self.replace = replace;
+        self
+    }
+
+    /// Sets how many lines `write` prints before and after each match.
+    /// 
+    /// Context lines are printed as `number- text`. Lines shared by two
+    /// matches are printed once, and groups of lines that aren't next to
+    /// each other are split by the context separator. Context is left
+    /// out with `only_matching` and `replace` output.
+    /// 
+    pub fn with_context(mut self, before: usize, after: usize) -> CommandArgs {
+        self.before_context = Some(before);
+        self.after_context = Some(after);
+        self
+    }
+
+    /// Sets whether `write` prints the changes `replace` would make as a
+    /// unified diff, in place of the matching lines.
+    /// 
+    /// The file itself is never changed, but the diff can be applied with
+    /// `patch -p0`.
+    /// 
+    pub fn with_diff(mut self, diff: bool) -> CommandArgs {
+        self.diff = diff;
+        self
+    }
+
+    /// Sets the line printed between groups of context lines, or `None`
+    /// to print nothing between them.
+    /// 
+    /// The default is `--`, as in grep.
+    /// 
+    pub fn with_context_separator(mut self, separator: Option<String>) -> CommandArgs {
+        match separator {
+            Some(separator) => {
+                self.context_separator = separator;
+                self.no_context_separator = false;
+            }
+            None => self.no_context_separator = true,
+        }
+        self
+    }
+
+    // Returns the number printed for the first line, 1 unless set.
+    fn line_number_start(&self) -> usize {
+        self.line_number_start.unwrap_or(1)
+    }
+
+    /// Returns the language of messages: `--lang`, then the locale unless
+    /// the output should be deterministic.
+    ///
+    pub fn lang(&self) -> Lang {
+        match (self.lang, self.deterministic) {
+            (Some(lang), _) => lang,
+            (None, true) => Lang::En,
+            (None, false) => Lang::from_env(),
+        }
+    }
+
+    /// Returns true if the query matches the file path.
+    /// 
+    /// The path is matched like a line of contents, so every option that
+    /// affects matching, including `invert_match`, applies.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::CommandArgs;
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("main".to_string(), PathBuf::from("src/main.rs"), false, false, true, false);
+    /// 
+    /// assert!(args.matches_path());
+    /// ```
+    /// 
+    pub fn matches_path(&self) -> bool {
+        let path = self.path.to_string_lossy();
+        new_matcher(self).is_ok_and(|matcher| matcher.find(&Prepared::new(&path, Folding::new(self))).is_some() != self.invert_match)
+    }
+
+    // Returns true if the path matches the `--invert-file-match` pattern.
+    fn skips_path(&self) -> bool {
+        let Some(pattern) = &self.invert_file_match else {
+            return false;
+        };
+        let path_query = CommandArgs {
+            query: pattern.clone(),
+            any_of: Vec::new(),
+            all_of: Vec::new(),
+            none_of: Vec::new(),
+            near: None,
+            invert_match: false,
+            ..self.clone()
+        };
+        path_query.matches_path()
+    }
+
+    // Runs the search once the patterns are in their final form.
+    fn search_file(&self) -> Result<(), Box<dyn Error>> {
+        if self.explain {
+            let mut stdout = std::io::stdout().lock();
+            for line in explain(self) {
+                writeln!(stdout, "{line}")?;
+            }
+            return Ok(());
+        }
+        if self.skips_path() {
+            diagnose(self, "skipped, the path matches --invert-file-match");
+            return Ok(());
+        }
+        if self.git_history {
+            return write_history(self, &mut std::io::stdout());
+        }
+        if self.filename_only {
+            if self.matches_path() {
+                write!(std::io::stdout(), "{}{}", self.path.display(), name_terminator(self))?;
+            }
+            return Ok(());
+        }
+        // With a single file, the overall and per-file timeouts both
+        // bound the same work.
+        let deadline = self.timeout.into_iter().chain(self.file_timeout).min().map(|timeout| Instant::now() + timeout);
+        let timed_out = |e: &(dyn Error + 'static)| e.is::<Cancelled>() && deadline.is_some_and(|d| Instant::now() >= d);
+        let contents = match timed(self.timing, "read", || read_before(self, deadline)) {
+            Ok(Some(contents)) => {
+                diagnose(self, describe_contents(self, &contents));
+                contents
+            }
+            Ok(None) => return report_timeout(self),
+            Err(e) => {
+                if !self.no_messages {
+                    eprintln!("grepr: {}: {e}", self.path.display());
+                }
+                return Err(Box::new(ReadErrors));
+            }
+        };
+        let mut search = Search::new(&contents);
+        if self.progress && std::io::stderr().is_terminal() {
+            let total = contents.len();
+            search.set_progress(move |progress| render_progress(progress, total));
+        }
+        let token = interrupt_token();
+        if let Some(deadline) = deadline {
+            token.cancel_after(deadline.saturating_duration_since(Instant::now()));
+        }
+        search.set_cancellation(token);
+        if self.count_matches {
+            let count = timed(self.timing, "match", || search.count_matches(self));
+            if count.as_ref().is_err_and(|e| timed_out(e.as_ref())) {
+                return report_timeout(self);
+            }
+            writeln!(std::io::stdout(), "{}", count?)?;
+            return Ok(());
+        }
+        if self.count_per_pattern {
+            let counts = timed(self.timing, "match", || search.count_per_pattern(self));
+            if counts.as_ref().is_err_and(|e| timed_out(e.as_ref())) {
+                return report_timeout(self);
+            }
+            for (pattern, count) in iter::once(&self.query).chain(&self.any_of).zip(counts?) {
+                writeln!(std::io::stdout(), "{pattern}: {count}")?;
+            }
+            return Ok(());
+        }
+        if self.files_with_all_matches {
+            let matched = timed(self.timing, "match", || search.matches_every_pattern(self));
+            if matched.as_ref().is_err_and(|e| timed_out(e.as_ref())) {
+                return report_timeout(self);
+            }
+            if matched? {
+                write!(std::io::stdout(), "{}{}", self.path.display(), name_terminator(self))?;
+            }
+            return Ok(());
+        }
+        if self.json_input {
+            let found = timed(self.timing, "match", || search.find_json(self));
+            if found.as_ref().is_err_and(|e| timed_out(e.as_ref())) {
+                return report_timeout(self);
+            }
+            let highlighter = Highlighter::new(self, colors_enabled())?;
+            let mut stdout = std::io::stdout().lock();
+            for m in found? {
+                writeln!(stdout, "{}:{}: {}", printed_number(self, m.number), m.pointer, highlighter.highlight(&m.value))?;
+            }
+            return Ok(());
+        }
+        if self.frequencies {
+            let frequencies = timed(self.timing, "match", || search.frequencies(self));
+            if frequencies.as_ref().is_err_and(|e| timed_out(e.as_ref())) {
+                return report_timeout(self);
+            }
+            let mut stdout = std::io::stdout().lock();
+            for (text, count) in frequencies? {
+                writeln!(stdout, "{count:>7} {text}")?;
+            }
+            return Ok(());
+        }
+        // An interrupted search still prints the results found so far.
+        let cached = self.cache && cache::load(self, &mut search);
+        match cached {
+            true => diagnose(self, "reusing cached results"),
+            false => diagnose(self, format!("matching with the {:?} engine", engine(self)).to_lowercase()),
+        }
+        let found = match cached {
+            true => Ok(()),
+            false => timed(self.timing, "match", || search.find(self)),
+        };
+        if found.as_ref().is_err_and(|e| timed_out(e.as_ref())) {
+            return report_timeout(self);
+        }
+        let interrupted = found.as_ref().is_err_and(|e| e.is::<Cancelled>());
+        if !interrupted {
+            found?;
+            if !cached && self.cache {
+                cache::store(self, &search);
+            }
+        }
+        timed(self.timing, "print", || search.write(self, &mut std::io::stdout().lock()))?;
+        if self.stats {
+            write_stats(&search.get_stats(), self.lang(), &mut std::io::stdout())?;
+        }
+        match interrupted {
+            true => Err(Box::new(Cancelled)),
+            false => Ok(()),
+        }
+    }
+}
+
+impl RunArgs for CommandArgs {
+    /// Executes the search process given the command line arguments.
+    /// 
+    /// Reads contents of the specified file and generates a new 
+    /// `Search` struct to store the results. Once completed, the results
+    /// are written to the terminal.
+    /// 
+    /// # Returns
+    /// Returns () if successful.
+    /// 
+    fn run(&self) -> Result<(), Box<dyn Error>> {
+        #[cfg(feature = "cli")]
+        if self.deterministic {
+            colored::control::set_override(false);
+        }
+        let shifted;
+        let args = match self.line_range {
+            Some(range) if self.line_number_start() > 0 => {
+                let start = self.line_number_start();
+                let range = LineRange { start: range.start.map(|n| n.saturating_sub(start)), end: range.end.map(|n| n.saturating_sub(start)) };
+                shifted = CommandArgs { line_range: Some(range), ..self.clone() };
+                &shifted
+            }
+            _ => self,
+        };
+        if args.binary {
+            return binary_patterns(args)?.search_file();
+        }
+        args.search_file()
+    }
+
+    /// Reads and stores the contents of a file.
+    /// 
+    /// # Returns
+    /// Returns the contents of a file as a `String`, or an error if the
+    /// path is a FIFO, socket or device and `devices` is `Skip`. With
+    /// `binary`, each byte becomes the character with the same value.
+    /// 
+    fn read(&self) -> Result<String, Box<dyn Error>> {
+        let lang = self.lang();
+        let file_type = fs::metadata(&self.path).map_err(|e| lang.io_error(e))?.file_type();
+        if self.devices == Devices::Skip && !file_type.is_file() && !file_type.is_dir() {
+            return Err(lang.text(Message::SkippedSpecialFile).into());
+        }
+        Ok(decode(self, fs::read(&self.path).map_err(|e| lang.io_error(e))?)?)
+    }
+}
+
+
+/// Defines methods expected to run on `Search`.
+pub trait IsSearch {
+    /// Searchs for the query in the file contents.
+    fn find(&mut self, args: &CommandArgs) -> Result<(), Box<dyn Error>>;
+}
+
+impl<'a> Search<'a> {
+    /// Creates a new `Search`.
+    /// 
+    /// # Returns
+    /// Returns a `Search` containing a reference to `contents` 
+    /// and an empty `results` vector.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::Search;
+    /// let some_text = "This is a test.\n With two lines.".to_string();
+    /// 
+    /// let new_search = Search::new(&some_text);
+    /// ```
+    /// 
+    pub fn new(contents: &'a str) -> Search<'a> {
+        Search {
+            contents,
+            results: Vec::new(),
+            matches: Vec::new(),
+            cancel: None,
+            progress: None,
+            stats: Cell::new(Stats::default()),
+        }
+    }
+
+    /// Sets a callback that receives `Progress` updates while searching.
+    /// 
+    /// The callback is called roughly once per `PROGRESS_INTERVAL` bytes
+    /// and once more when the search completes. The contents of a
+    /// `Search` count as a single file.
+    /// 
+    pub fn set_progress(&mut self, callback: impl Fn(&Progress) + 'a) {
+        self.progress = Some(Box::new(callback));
+    }
+
+    /// Sets a token that is checked before each line is searched.
+    /// 
+    /// Once the token is cancelled, `find` and `find_with` stop and
+    /// return a `Cancelled` error. Results found before that point are
+    /// kept by `find`.
+    /// 
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.cancel = Some(token);
+    }
+
+    /// Writes the search results to the command line.
+    /// 
+    /// The output is buffered, and flushed after every line if
+    /// `line_buffered` is set.
+    /// 
+    fn write(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        let mut writer: Box<dyn std::io::Write + '_> = match args.line_buffered {
+            true => Box::new(LineWriter::new(writer)),
+            false => Box::new(BufWriter::new(writer)),
+        };
+        self.write_results(args, &mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    // Writes the results in the format selected by `args`.
+    fn write_results(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        if let Some(number) = args.field {
+            return self.write_fields(args, writer, number);
+        }
+        if let Some(context) = args.hex_context.filter(|_| args.binary) {
+            return self.write_hexdump(args, writer, context);
+        }
+        if let Some(template) = &args.format_template {
+            return self.write_template(args, writer, template);
+        }
+        match args.format.unwrap_or_default() {
+            OutputFormat::Text => {}
+            OutputFormat::Csv => return self.write_table(args, writer, ','),
+            OutputFormat::Tsv => return self.write_table(args, writer, '\t'),
+            OutputFormat::Sarif => return self.write_sarif(args, writer),
+            OutputFormat::Github => return self.write_github(args, writer),
+            OutputFormat::Emacs => return self.write_emacs(args, writer),
+        }
+        if args.diff {
+            return self.write_diff(args, writer);
+        }
+        let highlighter = Highlighter::new(args, colors_enabled())?;
+        let extractor = Extractor::new(args)?;
+        let path = args.hyperlink_format.as_ref().map(|_| fs::canonicalize(&args.path).unwrap_or(args.path.clone()));
+        let link = |text: String, line: usize, column: usize| match (&args.hyperlink_format, &path) {
+            (Some(format), Some(path)) => hyperlink(&text, &hyperlink_url(format, path, line, column)),
+            _ => text,
+        };
+        write!(writer, "{}{}", link(args.path.display().to_string(), 1, 1), name_terminator(args))?;
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        if args.dedupe {
+            for m in &self.matches {
+                *counts.entry(m.line).or_default() += 1;
+            }
+        }
+        let headings = match args.show_function || args.md_scope.is_some() {
+            true => heading::headings(&args.path, self.contents),
+            false => Vec::new(),
+        };
+        let (before, after) = match extractor {
+            Some(_) => (0, 0),
+            None => (args.before_context.or(args.context).unwrap_or(0), args.after_context.or(args.context).unwrap_or(0)),
+        };
+        let separator = (before + after > 0 && !args.no_context_separator).then_some(&args.context_separator);
+        let matched: HashSet<usize> = match after {
+            0 => HashSet::new(),
+            _ => self.matches.iter().map(|m| m.number).collect(),
+        };
+        let write_context = |writer: &mut dyn std::io::Write, line: &Line| {
+            let (text, _) = layout(&Match { number: line.number, line: line.text, span: None }, args);
+            writeln!(writer, "{}- {text}", printed_number(args, line.number))
+        };
+        let mut last_printed: Option<usize> = None;
+        let mut last_heading = None;
+        let mut seen = HashSet::new();
+        for m in &self.matches {
+            if args.dedupe && !seen.insert(m.line) {
+                continue;
+            }
+            let context = self.context(m, before, after);
+            let lines_before: Vec<&Line> = context.before.iter().filter(|line| last_printed.is_none_or(|last| line.number > last)).collect();
+            let first = lines_before.first().map_or(m.number, |line| line.number);
+            if let (Some(last), Some(separator)) = (last_printed, separator) {
+                if first > last + 1 {
+                    writeln!(writer, "{separator}")?;
+                }
+            }
+            for line in lines_before {
+                write_context(writer, line)?;
+            }
+            if let Some(&(number, heading)) = heading::heading_of(&headings, m.number) {
+                if last_heading.replace(number) != Some(number) {
+                    writeln!(writer, "{}= {heading}", printed_number(args, number))?;
+                }
+            }
+            let number = link(printed_number(args, m.number).to_string(), m.number + 1, m.char_column().unwrap_or(1));
+            let mut prefix = format!("{number}:");
+            if args.column {
+                prefix.push_str(&format!("{}:", m.char_column().unwrap_or(0)));
+            }
+            if args.byte_column {
+                prefix.push_str(&format!("{}:", m.byte_column().unwrap_or(0)));
+            }
+            let (line, span) = layout(m, args);
+            let m = &Match { line: &line, span, ..*m };
+            let texts = match &extractor {
+                Some(extractor) => extractor.extract(m.line),
+                None => vec![match args.max_columns {
+                    Some(max) if m.line.chars().count() > max => match args.max_columns_preview {
+                        true => format!("{} [... omitted]", highlighter.highlight(&preview(m, max))),
+                        false => "[omitted long line]".to_string(),
+                    },
+                    _ => highlighter.highlight(m.line),
+                }],
+            };
+            for text in texts {
+                match counts.get(m.line) {
+                    Some(&count) if args.dedupe_count && count > 1 => writeln!(writer, "{prefix} {text} (x{count})")?,
+                    _ => writeln!(writer, "{prefix} {text}")?,
+                }
+            }
+            last_printed = Some(m.number);
+            // A later match within the lines after is printed as a match,
+            // with its own context.
+            for line in context.after.iter().take_while(|line| !matched.contains(&line.number)) {
+                write_context(writer, line)?;
+                last_printed = Some(line.number);
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the file offset of each match and a hexdump of the bytes
+    // around it. The contents are expected to hold one byte per character,
+    // as read with `binary`.
+    fn write_hexdump(&self, args: &CommandArgs, writer: &mut impl std::io::Write, context: usize) -> Result<(), Box<dyn Error>> {
+        let bytes: Vec<u8> = self.contents.chars().map(|c| c as u32 as u8).collect();
+        // `offset` is the file offset of the character at `position`.
+        let mut position = 0;
+        let mut offset = 0;
+        for m in &self.matches {
+            let line_start = m.line.as_ptr() as usize - self.contents.as_ptr() as usize;
+            offset += self.contents[position..line_start].chars().count();
+            position = line_start;
+            let span = m.span.unwrap_or(Span { start: 0, end: m.line.len() });
+            let start = offset + m.line[..span.start].chars().count();
+            let end = offset + m.line[..span.end].chars().count();
+            writeln!(writer, "{}: match at 0x{start:08x}", printed_number(args, m.number))?;
+            let from = start.saturating_sub(context);
+            let to = (end + context).min(bytes.len());
+            for (row, chunk) in bytes[from..to].chunks(16).enumerate() {
+                writeln!(writer, "{}", hexdump_row(from + row * 16, chunk))?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes one field of each result, with nothing else on the line.
+    fn write_fields(&self, args: &CommandArgs, writer: &mut impl std::io::Write, number: usize) -> Result<(), Box<dyn Error>> {
+        for m in &self.matches {
+            let field = match (number, &args.delimiter) {
+                (0, _) => Some(m.line),
+                (n, Some(delimiter)) => m.line.split(delimiter.as_str()).nth(n - 1),
+                (n, None) => m.line.split_whitespace().nth(n - 1),
+            };
+            writeln!(writer, "{}", field.unwrap_or(""))?;
+        }
+        Ok(())
+    }
+
+    // Writes the search results as rows of delimiter separated values.
+    fn write_table(&self, args: &CommandArgs, writer: &mut impl std::io::Write, delimiter: char) -> Result<(), Box<dyn Error>> {
+        let path = args.path.display().to_string();
+        let field = |value: &str| match delimiter {
+            ',' => csv_field(value),
+            _ => tsv_field(value),
+        };
+        let header = ["path", "line", "column", "match", "line_text"];
+        writeln!(writer, "{}", header.join(&delimiter.to_string()))?;
+        for m in &self.matches {
+            let column = m.char_column().map(|column| column.to_string()).unwrap_or_default();
+            let matched = m.span.map_or("", |span| &m.line[span.start..span.end]);
+            let row = [field(&path), printed_number(args, m.number).to_string(), column, field(matched), field(m.line)];
+            writeln!(writer, "{}", row.join(&delimiter.to_string()))?;
+        }
+        Ok(())
+    }
+
+    // Writes the search results as a SARIF log on a single line.
+    // Each pattern is a rule, and a result's rule is the first pattern
+    // found on its line. SARIF lines and columns are 1-based.
+    fn write_sarif(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        let rules: Vec<String> = iter::once(&args.query).chain(&args.any_of).map(|p| json_string(p)).collect();
+        let matchers = pattern_matchers(args)?;
+        let folding = Folding::new(args);
+        let uri = json_string(&percent_encode(&args.path.to_string_lossy()));
+        let results: Vec<String> = self.matches.iter().map(|m| {
+            let line = Prepared::new(m.line, folding);
+            let rule = &rules[matchers.iter().position(|matcher| matcher.find(&line).is_some()).unwrap_or(0)];
+            let mut region = format!("\"startLine\":{}", printed_number(args, m.number));
+            if let Some(span) = m.span {
+                let start = m.line[..span.start].chars().count() + 1;
+                let end = m.line[..span.end].chars().count() + 1;
+                region.push_str(&format!(",\"startColumn\":{start},\"endColumn\":{end}"));
+            }
+            format!(
+                "{{\"ruleId\":{rule},\"level\":\"warning\",\"message\":{{\"text\":{}}},\"locations\":[{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{uri}}},\"region\":{{{region}}}}}}}]}}",
+                json_string(m.line),
+            )
+        }).collect();
+        writeln!(
+            writer,
+            "{{\"version\":\"2.1.0\",\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"runs\":[{{\"tool\":{{\"driver\":{{\"name\":\"grepr\",\"version\":\"{}\",\"rules\":[{}]}}}},\"results\":[{}]}}]}}",
+            env!("CARGO_PKG_VERSION"),
+            rules.iter().map(|rule| format!("{{\"id\":{rule}}}")).collect::<Vec<_>>().join(","),
+            results.join(","),
+        )?;
+        Ok(())
+    }
+
+    // Writes each match as a GitHub Actions warning annotation.
+    fn write_github(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        let file = github_escape(&args.path.to_string_lossy(), true);
+        for m in &self.matches {
+            write!(writer, "::warning file={file},line={}", printed_number(args, m.number))?;
+            if let Some(span) = m.span {
+                let start = m.line[..span.start].chars().count() + 1;
+                let end = m.line[..span.end].chars().count() + 1;
+                write!(writer, ",col={start},endColumn={end}")?;
+            }
+            writeln!(writer, "::{}", github_escape(m.line, false))?;
+        }
+        Ok(())
+    }
+
+    // Writes each match as `file:line:column: text`, with printed line
+    // numbers, 1-based character columns and no colors.
+    fn write_emacs(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        let file = args.path.display();
+        for m in &self.matches {
+            writeln!(writer, "{file}:{}:{}: {}", printed_number(args, m.number), m.char_column().unwrap_or(1), m.line)?;
+        }
+        Ok(())
+    }
+
+    // Writes the lines `--replace` changes as a unified diff, with three
+    // lines of context around each change. Diff line numbers are always
+    // 1-based. Nothing is written if no line changes.
+    fn write_diff(&self, args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+        const CONTEXT: usize = 3;
+        let Some(extractor) = Extractor::new(args)? else {
+            return Ok(());
+        };
+        let changes: Vec<(usize, String)> = self.matches
+            .iter()
+            .filter_map(|m| {
+                let replaced = extractor.extract(m.line).pop()?;
+                (replaced != m.line).then_some((m.number, replaced))
+            })
+            .collect();
+        if changes.is_empty() {
+            return Ok(());
+        }
+        let lines: Vec<&str> = self.contents.lines().collect();
+        let path = args.path.display();
+        writeln!(writer, "--- {path}\n+++ {path}")?;
+        let mut shift: isize = 0;
+        let mut index = 0;
+        while index < changes.len() {
+            // A hunk takes in every change within two contexts of the last.
+            let mut last = index;
+            while changes.get(last + 1).is_some_and(|next| next.0 - changes[last].0 <= 2 * CONTEXT) {
+                last += 1;
+            }
+            let start = changes[index].0.saturating_sub(CONTEXT);
+            let end = (changes[last].0 + CONTEXT + 1).min(lines.len());
+            let mut body = Vec::new();
+            let mut added = 0;
+            let mut change = changes[index..=last].iter().peekable();
+            for (number, line) in lines.iter().enumerate().take(end).skip(start) {
+                match change.next_if(|(changed, _)| *changed == number) {
+                    Some((_, replaced)) => {
+                        body.push(format!("-{line}"));
+                        for new_line in replaced.split('\n') {
+                            body.push(format!("+{new_line}"));
+                            added += 1;
+                        }
+                    }
+                    None => {
+                        body.push(format!(" {line}"));
+                        added += 1;
+                    }
+                }
+            }
+            let old_len = end - start;
+            writeln!(writer, "@@ -{},{old_len} +{},{added} @@", start + 1, start as isize + 1 + shift)?;
+            for line in body {
+                writeln!(writer, "{line}")?;
+            }
+            shift += added as isize - old_len as isize;
+            index = last + 1;
+        }
+        Ok(())
+    }
+
+    // Writes each match by expanding a user template.
+    // The template is checked first, so a bad one fails even without matches.
+    fn write_template(&self, args: &CommandArgs, writer: &mut impl std::io::Write, template: &str) -> Result<(), Box<dyn Error>> {
+        let path = args.path.display().to_string();
+        let empty = Match { number: 0, line: "", span: None };
+        expand_template(template, |name| template_field(name, &path, &empty, args))?;
+        for m in &self.matches {
+            let line = expand_template(template, |name| template_field(name, &path, m, args))?;
+            writeln!(writer, "{line}")?;
+        }
+        Ok(())
+    }
+
+    // Returns the numbered lines of the contents inside `range`.
+    // Lines before the range are skipped without being matched, and
+    // iteration stops at the end of the range.
+    fn numbered_lines(&self, range: LineRange) -> impl Iterator<Item = (usize, &'a str)> {
+        let lines = self.contents.lines().enumerate().skip(range.start.unwrap_or(0));
+        lines.take_while(move |&(number, _)| range.end.is_none_or(|end| number <= end))
+    }
+
+    /// Returns the raw results vector from `Search`.
+    pub fn get_results(&self) -> &Vec<(usize, &'a str)> {
+        &self.results
+    }
+
+    /// Returns the counts collected by the most recent search.
+    pub fn get_stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Returns every `Match` found by `find`, including match locations.
+    pub fn get_matches(&self) -> &Vec<Match<'a>> {
+        &self.matches
+    }
+
+    /// Returns the byte range of the whole line of a match within the
+    /// contents.
+    /// 
+    /// The range excludes the line break. For a file read as UTF-8 text
+    /// these are also offsets into the file, so an edit can be spliced
+    /// in without counting lines again. `m` must be a match found in
+    /// these contents.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, IsSearch, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("b".to_string(), PathBuf::new(), false, false, false, false);
+    /// let contents = "aa\r\nbbb\n";
+    /// let mut search = Search::new(contents);
+    /// let _ = search.find(&args);
+    /// let range = search.byte_range(&search.get_matches()[0]);
+    /// 
+    /// assert_eq!(range, 4..7);
+    /// assert_eq!(&contents[range], "bbb");
+    /// ```
+    /// 
+    pub fn byte_range(&self, m: &Match<'a>) -> Range<usize> {
+        let start = m.line.as_ptr() as usize - self.contents.as_ptr() as usize;
+        start..start + m.line.len()
+    }
+
+    /// Returns up to `before` lines before a match and `after` lines
+    /// after it, so a host can show context its own way.
+    /// 
+    /// The two counts are independent. Fewer lines are returned at the
+    /// start and end of the contents. `m` must be a match found in these
+    /// contents, such as one from `get_matches` or `find_with`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, IsSearch, Line, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("error".to_string(), PathBuf::new(), false, false, false, false);
+    /// let mut search = Search::new("start\nload\nerror\nretry");
+    /// let _ = search.find(&args);
+    /// let context = search.context(&search.get_matches()[0], 2, 1);
+    /// 
+    /// assert_eq!(context.before, vec![Line { number: 0, text: "start" }, Line { number: 1, text: "load" }]);
+    /// assert_eq!(context.after, vec![Line { number: 3, text: "retry" }]);
+    /// ```
+    /// 
+    pub fn context(&self, m: &Match<'a>, before: usize, after: usize) -> Context<'a> {
+        let Range { start, end } = self.byte_range(m);
+        let mut lines_before: Vec<Line<'a>> = self.contents[..start].lines().rev().take(before).zip((0..m.number).rev())
+            .map(|(text, number)| Line { number, text })
+            .collect();
+        lines_before.reverse();
+        let rest = &self.contents[end..];
+        let rest = rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n')).unwrap_or("");
+        let lines_after = rest.lines().take(after).zip(m.number + 1..)
+            .map(|(text, number)| Line { number, text })
+            .collect();
+        Context { before: lines_before, after: lines_after }
+    }
+
+    /// Searches the contents for several independent queries at once.
+    /// 
+    /// The contents are scanned a single time, with every line checked
+    /// against each query in turn. Each line is only prepared once for
+    /// every distinct combination of case folding and normalization.
+    /// The `path` of each query is ignored.
+    /// 
+    /// # Returns
+    /// Returns one results vector per query, in the same order as `queries`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::path::PathBuf;
+    /// let queries = [
+    ///     CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false),
+    ///     CommandArgs::new("another".to_string(), PathBuf::new(), false, false, true, false),
+    /// ];
+    /// let search = Search::new("this is a test.\nthis is another test!");
+    /// 
+    /// let results = search.find_many(&queries).unwrap();
+    /// assert_eq!(results[0].len(), 2);
+    /// assert_eq!(results[1], vec![(1, "this is another test!")]);
+    /// ```
+    /// 
+    pub fn find_many(&self, queries: &[CommandArgs]) -> Result<Vec<Results<'a>>, Box<dyn Error>> {
+        let matchers = queries.iter().map(new_matcher).collect::<Result<Vec<_>, _>>()?;
+        let mut windows = queries.iter().map(TimeWindow::new).collect::<Result<Vec<_>, _>>()?;
+        let mut scopes: Vec<_> = queries.iter().map(ScopeFilter::new).collect();
+        let foldings: Vec<Folding> = queries.iter().map(Folding::new).collect();
+        let mut results = vec![Vec::new(); queries.len()];
+        for (number, line) in self.contents.lines().enumerate() {
+            if self.cancel.as_ref().is_some_and(|token| token.is_cancelled()) {
+                return Err(Box::new(Cancelled));
+            }
+            let mut lines: Vec<(Folding, Prepared)> = Vec::new();
+            for (index, args) in queries.iter().enumerate() {
+                if args.line_range.is_some_and(|range| !range.contains(number)) {
+                    continue;
+                }
+                if windows[index].as_mut().is_some_and(|window| !window.accepts(line)) {
+                    continue;
+                }
+                if scopes[index].as_mut().is_some_and(|scope| !scope.accepts(line)) {
+                    continue;
+                }
+                let folding = foldings[index];
+                let position = match lines.iter().position(|(f, _)| *f == folding) {
+                    Some(position) => position,
+                    None => {
+                        lines.push((folding, Prepared::new(line, folding)));
+                        lines.len() - 1
+                    }
+                };
+                let search_line = &lines[position].1;
+                if matchers[index].find(search_line).is_some() != args.invert_match {
+                    results[index].push((number, line));
+                }
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Counts every match of the query in the contents.
+    /// 
+    /// Unlike the number of results, a line with several matches counts
+    /// once for each of them. With `invert_match`, each non-matching line
+    /// counts once.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("is".to_string(), PathBuf::new(), false, false, false, false);
+    /// let search = Search::new("this is a test.\nthis is another test!");
+    /// 
+    /// assert_eq!(search.count_matches(&args).unwrap(), 4);
+    /// ```
+    /// 
+    pub fn count_matches(&self, args: &CommandArgs) -> Result<usize, Box<dyn Error>> {
+        let query = CompiledQuery::new(args)?;
+        let mut buffer = LineBuffer::default();
+        let mut count = 0;
+        let _ = self.find_compiled(&query, |m| {
+            count += match args.invert_match {
+                true => 1,
+                false => {
+                    let searched = limit_line(m.line, args, &mut Stats::default()).unwrap_or(m.line);
+                    query.matcher.find_all(&buffer.prepare(searched, query.folding)).len()
+                }
+            };
+            ControlFlow::<()>::Continue(())
+        })?;
+        Ok(count)
+    }
+
+    /// Counts the results matched by each pattern.
+    /// 
+    /// The query comes first, followed by each `any_of` pattern. A result
+    /// counts once for every pattern found in it, so the counts can add
+    /// up to more than the number of results.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("error".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_any_of(vec!["warn".to_string()]);
+    /// let search = Search::new("error: disk\nwarn: cpu\nerror, warn: net");
+    /// 
+    /// assert_eq!(search.count_per_pattern(&args).unwrap(), vec![2, 2]);
+    /// ```
+    /// 
+    pub fn count_per_pattern(&self, args: &CommandArgs) -> Result<Vec<usize>, Box<dyn Error>> {
+        let query = CompiledQuery::new(args)?;
+        let matchers = pattern_matchers(args)?;
+        let mut buffer = LineBuffer::default();
+        let mut counts = vec![0; matchers.len()];
+        let _ = self.find_compiled(&query, |m| {
+            let searched = limit_line(m.line, args, &mut Stats::default()).unwrap_or(m.line);
+            let line = buffer.prepare(searched, query.folding);
+            for (count, matcher) in counts.iter_mut().zip(&matchers) {
+                *count += usize::from(matcher.find(&line).is_some());
+            }
+            ControlFlow::<()>::Continue(())
+        })?;
+        Ok(counts)
+    }
+
+    /// Returns true if the query and every `any_of` pattern each match
+    /// at least one result.
+    /// 
+    /// Unlike `all_of`, the patterns don't have to be on the same line.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("Deserialize".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_any_of(vec!["unsafe".to_string()]);
+    /// 
+    /// assert!(Search::new("#[derive(Deserialize)]\nunsafe { read() }").matches_every_pattern(&args).unwrap());
+    /// assert!(!Search::new("#[derive(Deserialize)]").matches_every_pattern(&args).unwrap());
+    /// ```
+    /// 
+    pub fn matches_every_pattern(&self, args: &CommandArgs) -> Result<bool, Box<dyn Error>> {
+        Ok(self.count_per_pattern(args)?.iter().all(|&count| count > 0))
+    }
+
+    /// Counts how often each distinct text was matched.
+    /// 
+    /// Every match on a line counts, as with `count_matches`. With
+    /// `invert_match` there is no matched text, so nothing is counted.
+    /// 
+    /// # Returns
+    /// Returns each matched text with its count, from most to least
+    /// common. Texts with the same count are sorted alphabetically.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("error".to_string(), PathBuf::new(), true, false, false, false);
+    /// let search = Search::new("Error: disk\nerror: cpu\nERROR, error: net");
+    /// 
+    /// let expected = vec![("error".to_string(), 2), ("ERROR".to_string(), 1), ("Error".to_string(), 1)];
+    /// assert_eq!(search.frequencies(&args).unwrap(), expected);
+    /// ```
+    /// 
+    pub fn frequencies(&self, args: &CommandArgs) -> Result<Vec<(String, usize)>, Box<dyn Error>> {
+        let query = CompiledQuery::new(args)?;
+        let mut buffer = LineBuffer::default();
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        let _ = self.find_compiled(&query, |m| {
+            if !args.invert_match {
+                let searched = limit_line(m.line, args, &mut Stats::default()).unwrap_or(m.line);
+                for span in query.matcher.find_all(&buffer.prepare(searched, query.folding)) {
+                    *counts.entry(&searched[span.start..span.end]).or_default() += 1;
+                }
+            }
+            ControlFlow::<()>::Continue(())
+        })?;
+        let mut counts: Vec<(String, usize)> = counts.into_iter().map(|(text, count)| (text.to_string(), count)).collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Ok(counts)
+    }
+
+    /// Searches the values of JSON documents, one per line.
+    /// 
+    /// Each value is matched on its own, so the query can't match across
+    /// two values or a key. Lines that aren't valid JSON are skipped.
+    /// 
+    /// # Returns
+    /// Returns every matching value with its line number and JSON
+    /// pointer.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("timeout".to_string(), PathBuf::new(), false, false, false, false)
+    ///     .with_json_input(true, Some("/error".to_string()));
+    /// let search = Search::new(r#"{"msg": "timeout", "error": {"kind": "timeout"}}"#);
+    /// 
+    /// let found = search.find_json(&args).unwrap();
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!(found[0].pointer, "/error/kind");
+    /// ```
+    /// 
+    pub fn find_json(&self, args: &CommandArgs) -> Result<Vec<JsonMatch>, Box<dyn Error>> {
+        let query = CompiledQuery::new(args)?;
+        let under_path = |pointer: &str| match &args.json_path {
+            Some(path) => pointer.strip_prefix(path.as_str()).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')),
+            None => true,
+        };
+        let mut found = Vec::new();
+        for (number, line) in self.numbered_lines(args.line_range.unwrap_or_default()) {
+            if self.cancel.as_ref().is_some_and(|token| token.is_cancelled()) {
+                return Err(Box::new(Cancelled));
+            }
+            let Ok(values) = json::scalars(line) else {
+                continue;
+            };
+            for (pointer, value) in values.into_iter().filter(|(pointer, _)| under_path(pointer)) {
+                if query.matcher.find(&Prepared::new(&value, query.folding)).is_some() != args.invert_match {
+                    found.push(JsonMatch { number, pointer, value });
+                }
+            }
+        }
+        Ok(found)
+    }
+
+    /// Searches the contents and passes each result to `f` as it is found.
+    /// 
+    /// Unlike `find`, results are not stored in the `Search`. Returning
+    /// `ControlFlow::Break` from `f` stops the search early.
+    /// 
+    /// # Returns
+    /// Returns the `ControlFlow::Break` value if `f` stopped the search,
+    /// otherwise `ControlFlow::Continue(())`.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Search};
+    /// # use std::ops::ControlFlow;
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+    /// let search = Search::new("this is a test.\nthis is another test!");
+    /// 
+    /// let first = search.find_with(&args, |m| ControlFlow::Break(m.number)).unwrap();
+    /// assert_eq!(first, ControlFlow::Break(0));
+    /// ```
+    /// 
+    pub fn find_with<B, F>(&self, args: &CommandArgs, f: F) -> Result<ControlFlow<B>, Box<dyn Error>>
+    where
+        F: FnMut(Match<'a>) -> ControlFlow<B>,
+    {
+        self.find_compiled(&CompiledQuery::new(args)?, f)
+    }
+
+    /// Searches the contents with a query that was compiled beforehand.
+    /// 
+    /// Works like `find_with`, without preparing the query again.
+    /// 
+    pub fn find_compiled<B, F>(&self, query: &CompiledQuery, mut f: F) -> Result<ControlFlow<B>, Box<dyn Error>>
+    where
+        F: FnMut(Match<'a>) -> ControlFlow<B>,
+    {
+        let args = query.args;
+        if let Some(literal) = literal_query(args) {
+            return self.find_literal(literal, f);
+        }
+        let mut lines = LineSearch::new(query);
+        let mut progress = Progress { files_discovered: 1, ..Progress::default() };
+        let mut next_report = PROGRESS_INTERVAL;
+        let mut stats = Stats::default();
+        for (number, line) in self.numbered_lines(args.line_range.unwrap_or_default()) {
+            if self.cancel.as_ref().is_some_and(|token| token.is_cancelled()) {
+                self.stats.set(stats);
+                return Err(Box::new(Cancelled));
+            }
+            if let Some(callback) = &self.progress {
+                progress.bytes_scanned = line.as_ptr() as usize - self.contents.as_ptr() as usize;
+                if progress.bytes_scanned >= next_report {
+                    callback(&progress);
+                    next_report = progress.bytes_scanned + PROGRESS_INTERVAL;
+                }
+            }
+            if let Some(span) = lines.select(query, line, &mut stats) {
+                if let ControlFlow::Break(value) = f(Match { number, line, span }) {
+                    self.stats.set(stats);
+                    return Ok(ControlFlow::Break(value));
+                }
+            }
+        }
+        self.stats.set(stats);
+        if let Some(callback) = &self.progress {
+            progress.files_searched = 1;
+            progress.bytes_scanned = self.contents.len();
+            callback(&progress);
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Searches for the next page of at most `limit` results.
+    /// 
+    /// The search starts at `cursor`, so earlier lines are not searched
+    /// again and earlier results don't need to be kept. The exception is
+    /// a query with `--since`, `--until` or `--md-scope`, whose filters
+    /// depend on earlier lines; those are searched from the start.
+    /// 
+    /// # Returns
+    /// Returns the page, or an error if the query is invalid or the
+    /// search was cancelled.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, Cursor, Search};
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+    /// let search = Search::new("test 1\nnothing\ntest 2\ntest 3");
+    /// 
+    /// let first = search.find_page(&args, Cursor::default(), 2).unwrap();
+    /// assert_eq!(first.matches.iter().map(|m| m.number).collect::<Vec<_>>(), vec![0, 2]);
+    /// let second = search.find_page(&args, first.next.unwrap(), 2).unwrap();
+    /// assert_eq!(second.matches[0].line, "test 3");
+    /// assert_eq!(second.next, None);
+    /// ```
+    /// 
+    pub fn find_page(&self, args: &CommandArgs, cursor: Cursor, limit: usize) -> Result<Page<'a>, Box<dyn Error>> {
+        let stateful = args.since.is_some() || args.until.is_some() || args.md_scope.is_some();
+        let (base, rest) = match stateful {
+            true => (Cursor::default(), self.contents),
+            false => (cursor, self.contents.get(cursor.offset..).ok_or("cursor is past the end of the contents")?),
+        };
+        if args.line_range.and_then(|range| range.end).is_some_and(|end| end < base.number) {
+            return Ok(Page { matches: Vec::new(), next: None });
+        }
+        // `line_range` counts from the start of the contents, not of `rest`.
+        let line_range = args.line_range.map(|range| LineRange {
+            start: range.start.map(|start| start.saturating_sub(base.number)),
+            end: range.end.map(|end| end - base.number),
+        });
+        let shifted = CommandArgs { line_range, ..args.clone() };
+        let mut search = Search::new(rest);
+        search.cancel = self.cancel.clone();
+        let mut matches = Vec::new();
+        let flow = search.find_with(&shifted, |m| {
+            let m = Match { number: m.number + base.number, ..m };
+            if m.number >= cursor.number {
+                if matches.len() == limit {
+                    return ControlFlow::Break(());
+                }
+                matches.push(m);
+            }
+            ControlFlow::Continue(())
+        })?;
+        self.stats.set(search.get_stats());
+        let next = match (flow.is_break(), matches.last()) {
+            (false, _) => None,
+            (true, None) => Some(cursor),
+            (true, Some(last)) => {
+                let end = self.byte_range(last).end;
+                let line_break = match &self.contents[end..] {
+                    rest if rest.starts_with("\r\n") => 2,
+                    rest if rest.starts_with('\n') => 1,
+                    _ => 0,
+                };
+                Some(Cursor { number: last.number + 1, offset: end + line_break })
+            }
+        };
+        Ok(Page { matches, next })
+    }
+
+    // Finds a plain substring by scanning the whole contents for it, then
+    // works out the line around each hit. This is much faster than
+    // preparing and matching every line when hits are rare.
+    fn find_literal<B, F>(&self, query: &[u8], mut f: F) -> Result<ControlFlow<B>, Box<dyn Error>>
+    where
+        F: FnMut(Match<'a>) -> ControlFlow<B>,
+    {
+        let bytes = self.contents.as_bytes();
+        // Counts the lines in `bytes[..end]`, which ends after a line
+        // break or at the end of the contents, as `find_compiled` would
+        // have searched them.
+        let stats = |end: usize, lines_matched: usize| {
+            let searched = &bytes[..end];
+            let newlines = memchr_iter(b'\n', searched).count();
+            Stats {
+                lines_searched: newlines + usize::from(!searched.is_empty() && !searched.ends_with(b"\n")),
+                lines_matched,
+                bytes_searched: searched.len() - newlines - memmem::find_iter(searched, b"\r\n").count(),
+                ..Stats::default()
+            }
+        };
+        let mut lines_matched = 0;
+        let mut progress = Progress { files_discovered: 1, ..Progress::default() };
+        let mut next_report = PROGRESS_INTERVAL;
+        // `number` is the line number of the line starting at `counted`.
+        let mut number = 0;
+        let mut counted = 0;
+        let mut next_line = 0;
+        for hit in memmem::find_iter(bytes, query) {
+            if hit < next_line {
+                continue;
+            }
+            let start = memrchr(b'\n', &bytes[..hit]).map_or(0, |i| i + 1);
+            if self.cancel.as_ref().is_some_and(|token| token.is_cancelled()) {
+                self.stats.set(stats(start, lines_matched));
+                return Err(Box::new(Cancelled));
+            }
+            if let Some(callback) = &self.progress {
+                if hit >= next_report {
+                    progress.bytes_scanned = hit;
+                    callback(&progress);
+                    next_report = hit + PROGRESS_INTERVAL;
+                }
+            }
+            number += memchr_iter(b'\n', &bytes[counted..start]).count();
+            counted = start;
+            let line = self.contents[start..].lines().next().unwrap_or("");
+            next_line = start + line.len() + 1;
+            lines_matched += 1;
+            let span = Some(Span { start: hit - start, end: hit - start + query.len() });
+            if let ControlFlow::Break(value) = f(Match { number, line, span }) {
+                let end = memchr::memchr(b'\n', &bytes[start..]).map_or(bytes.len(), |i| start + i + 1);
+                self.stats.set(stats(end, lines_matched));
+                return Ok(ControlFlow::Break(value));
+            }
+        }
+        self.stats.set(stats(bytes.len(), lines_matched));
+        if let Some(callback) = &self.progress {
+            progress.files_searched = 1;
+            progress.bytes_scanned = self.contents.len();
+            callback(&progress);
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+impl<'q> CompiledQuery<'q> {
+    /// Compiles the query and options in `args`.
+    /// 
+    /// # Returns
+    /// Returns an error if an option such as `--since` is invalid.
+    /// 
+    /// # Example
+    /// ```
+    /// # use crate::grepr::{CommandArgs, CompiledQuery, Search};
+    /// # use std::ops::ControlFlow;
+    /// # use std::path::PathBuf;
+    /// let args = CommandArgs::new("test".to_string(), PathBuf::new(), true, false, false, false);
+    /// let query = CompiledQuery::new(&args).unwrap();
+    /// 
+    /// for contents in ["a TEST", "no match", "test"] {
+    ///     let search = Search::new(contents);
+    ///     let mut found = 0;
+    ///     let _ = search.find_compiled(&query, |_| {
+    ///         found += 1;
+    ///         ControlFlow::<()>::Continue(())
+    ///     });
+    ///     assert_eq!(found, usize::from(contents != "no match"));
+    /// }
+    /// ```
+    /// 
+    pub fn new(args: &'q CommandArgs) -> Result<CompiledQuery<'q>, Box<dyn Error>> {
+        Ok(CompiledQuery {
+            args,
+            folding: Folding::new(args),
+            matcher: new_matcher(args)?,
+            window: TimeWindow::new(args)?,
+            scope: ScopeFilter::new(args),
+        })
+    }
+
+    // Compiles the query like `new`, with the patterns decoded first if
+    // `args.binary` is set, for searches that decode their own lines.
+    pub(crate) fn decoded(args: &'q CommandArgs) -> Result<CompiledQuery<'q>, Box<dyn Error>> {
+        if !args.binary {
+            return CompiledQuery::new(args);
+        }
+        Ok(CompiledQuery {
+            args,
+            folding: Folding::new(args),
+            matcher: new_matcher(&binary_patterns(args)?)?,
+            window: TimeWindow::new(args)?,
+            scope: ScopeFilter::new(args),
+        })
+    }
+}
+
+impl LineSearch {
+    // Starts a pass of `query` over a new sequence of lines.
+    pub(crate) fn new(query: &CompiledQuery) -> LineSearch {
+        LineSearch { window: query.window.clone(), scope: query.scope.clone(), buffer: LineBuffer::default() }
+    }
+
+    // Checks the next line against `query`, counting it in `stats`.
+    // Returns the span to report if the line is selected, which is
+    // `None` for a line selected by `invert_match`.
+    pub(crate) fn select(&mut self, query: &CompiledQuery, line: &str, stats: &mut Stats) -> Option<Option<Span>> {
+        let args = query.args;
+        if self.window.as_mut().is_some_and(|window| !window.accepts(line)) {
+            return None;
+        }
+        if self.scope.as_mut().is_some_and(|scope| !scope.accepts(line)) {
+            return None;
+        }
+        let searched = limit_line(line, args, stats)?;
+        stats.lines_searched += 1;
+        stats.bytes_searched += searched.len();
+        let span = query.matcher.find(&self.buffer.prepare(searched, query.folding));
+        if span.is_some() == args.invert_match {
+            return None;
+        }
+        stats.lines_matched += 1;
+        Some(span.filter(|_| !args.invert_match))
+    }
+}
+
+impl<'a> Match<'a> {
+    /// Returns the 1-based byte column where the first match starts.
+    pub fn byte_column(&self) -> Option<usize> {
+        self.span.map(|span| span.start + 1)
+    }
+
+    /// Returns the 1-based character column where the first match starts.
+    /// 
+    /// Unlike `byte_column`, each multi-byte character counts as a
+    /// single column.
+    /// 
+    pub fn char_column(&self) -> Option<usize> {
+        self.span.map(|span| self.line[..span.start].chars().count() + 1)
+    }
+}
+
+impl CancellationToken {
+    /// Creates a new, uncancelled `CancellationToken`.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Requests that any search holding this token stops.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Cancels the token once `timeout` has passed.
+    /// 
+    /// The wait happens on a separate thread, so this returns straight
+    /// away.
+    /// 
+    pub fn cancel_after(&self, timeout: Duration) {
+        let token = self.clone();
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            token.cancel();
+        });
+    }
+
+    /// Returns true once `cancel` has been called on any clone.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "search cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+impl fmt::Display for ReadErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "some files could not be read")
+    }
+}
+
+impl Error for ReadErrors {}
+
+impl FromStr for Near {
+    type Err = String;
+
+    /// Parses `A,B:N` into two terms and a distance in words.
+    fn from_str(s: &str) -> Result<Near, String> {
+        let (terms, distance) = s.rsplit_once(':').ok_or("expected A,B:N")?;
+        let (first, second) = terms.split_once(',').ok_or("expected A,B:N")?;
+        let distance = distance.parse().map_err(|e| format!("invalid distance '{distance}': {e}"))?;
+        Ok(Near { first: first.to_string(), second: second.to_string(), distance })
+    }
+}
+
+impl LineRange {
+    /// Returns true if line `number` is inside the range.
+    pub fn contains(&self, number: usize) -> bool {
+        self.start.is_none_or(|start| number >= start) && self.end.is_none_or(|end| number <= end)
+    }
+}
+
+impl FromStr for LineRange {
+    type Err = String;
+
+    /// Parses `START:END`, where either bound may be empty.
+    fn from_str(s: &str) -> Result<LineRange, String> {
+        let (start, end) = s.split_once(':').ok_or("expected START:END")?;
+        let bound = |bound: &str| match bound {
+            "" => Ok(None),
+            _ => bound.parse().map(Some).map_err(|e| format!("invalid line number '{bound}': {e}")),
+        };
+        let range = LineRange { start: bound(start)?, end: bound(end)? };
+        match range {
+            LineRange { start: Some(start), end: Some(end) } if start > end => {
+                Err(format!("range starts after it ends: {start} > {end}"))
+            }
+            _ => Ok(range),
+        }
+    }
+}
+
+impl<'a> IsSearch for Search<'a> {
+    /// Searchs the file path for the query string.
+    fn find(&mut self, args: &CommandArgs) -> Result<(), Box<dyn Error>> {
+        if args.max_total == Some(0) {
+            return Ok(());
+        }
+        let mut matches = Vec::new();
+        let flow = self.find_with(args, |m| {
+            matches.push(m);
+            match args.max_total.is_some_and(|max| matches.len() >= max) {
+                true => ControlFlow::Break(()),
+                false => ControlFlow::Continue(()),
+            }
+        });
+        self.results.extend(matches.iter().map(|m| (m.number, m.line)));
+        self.matches.append(&mut matches);
+        let _ = flow?;
+
+        Ok(())
+    }
+}
+
+/// Searches a string slice without touching the file system.
+/// 
+/// This is the entry point for embedding the engine where there is no
+/// file system or terminal, such as `wasm32-unknown-unknown`. The
+/// `path` of `args` is ignored.
+/// 
+/// # Returns
+/// Returns the line number and text of every line selected by `args`.
+/// 
+/// # Example
+/// ```
+/// # use crate::grepr::{search_str, CommandArgs};
+/// # use std::path::PathBuf;
+/// let args = CommandArgs::new("another".to_string(), PathBuf::new(), false, false, false, false);
+/// 
+/// let results = search_str("this is a test.\nthis is another test!", &args).unwrap();
+/// assert_eq!(results, vec![(1, "this is another test!")]);
+/// ```
+/// 
+pub fn search_str<'a>(contents: &'a str, args: &CommandArgs) -> Result<Results<'a>, Box<dyn Error>> {
+    let mut search = Search::new(contents);
+    search.find(args)?;
+    Ok(search.results)
+}
+
+
+// helper methods
+
+// Colors used for `--any-of` patterns, in order.
+const PATTERN_COLORS: [&str; 5] = ["green", "blue", "magenta", "cyan", "yellow"];
+
+// Highlights the parts of lines of output that match the query or an
+// `--any-of` pattern, each in its own color. Matches are found by the
+// same matchers as the search, so `--word`, `--line`, `-i`, regular
+// expressions, fuzzy queries and folding all highlight what they matched.
+// Where matches overlap, the earliest one wins.
+struct Highlighter {
+    matchers: Vec<(Box<dyn Matcher>, String)>,
+    folding: Folding,
+    invert_match: bool,
+    paint: bool,
+}
+
+impl Highlighter {
+    // Builds the matchers for the patterns in `args`. Nothing is
+    // highlighted unless `paint` is set.
+    // Returns an error if `--match-color` isn't a known color or a
+    // pattern is invalid.
+    fn new(args: &CommandArgs, paint: bool) -> Result<Highlighter, Box<dyn Error>> {
+        let color = args.match_color.as_deref().unwrap_or("red");
+        check_color(color)?;
+        let colors = iter::once(color).chain(PATTERN_COLORS.into_iter().cycle()).map(String::from);
+        Ok(Highlighter {
+            matchers: pattern_matchers(args)?.into_iter().zip(colors).collect(),
+            folding: Folding::new(args),
+            invert_match: args.invert_match,
+            paint,
+        })
+    }
+
+    // Returns `line` with its matches highlighted. An inverted search
+    // selected lines where nothing matched, so there is nothing to
+    // highlight.
+    fn highlight(&self, line: &str) -> String {
+        if !self.paint || self.invert_match {
+            return line.to_string();
+        }
+        let prepared = Prepared::new(line, self.folding);
+        let spans = self.matchers
+            .iter()
+            .flat_map(|(matcher, color)| matcher.find_all(&prepared).into_iter().map(move |span| (span, color.as_str())))
+            .collect();
+        let mut highlighted = String::with_capacity(line.len());
+        let mut end = 0;
+        for (span, color) in first_spans(spans) {
+            highlighted.push_str(&line[end..span.start]);
+            highlighted.push_str(&paint_text(&line[span.start..span.end], color));
+            end = span.end;
+        }
+        highlighted.push_str(&line[end..]);
+        highlighted
+    }
+}
+
+// Rewrites matched lines of text output for `--only-matching` and
+// `--replace`.
+enum Extractor {
+    // Each match of the query or an `--any-of` pattern.
+    Matches(Vec<Box<dyn Matcher>>, Folding),
+    // One capture group of each match of the query.
+    Group(CaptureMatcher, usize),
+    // The line with each match of the query replaced by the template, or
+    // with `--only-matching` just the replacements.
+    Replace(CaptureMatcher, String, bool),
+}
+
+impl Extractor {
+    // Returns how `args` rewrites lines, or `None` if they are printed
+    // whole.
+    // Returns an error if the query is invalid, or has no group named by
+    // `--group`.
+    fn new(args: &CommandArgs) -> Result<Option<Extractor>, Box<dyn Error>> {
+        if let Some(template) = &args.replace {
+            return Ok(Some(Extractor::Replace(CaptureMatcher::new(args)?, template.clone(), args.only_matching)));
+        }
+        if !args.only_matching {
+            return Ok(None);
+        }
+        Ok(Some(match &args.group {
+            Some(group) => {
+                let captures = CaptureMatcher::new(args)?;
+                let index = captures.index(group)?;
+                Extractor::Group(captures, index)
+            }
+            None => Extractor::Matches(pattern_matchers(args)?, Folding::new(args)),
+        }))
+    }
+
+    // Returns the texts printed for a matched line, each on a line of
+    // its own. Empty matches are left out, except as replacements.
+    fn extract(&self, line: &str) -> Vec<String> {
+        let text = |span: Span| line[span.start..span.end].to_string();
+        match self {
+            Extractor::Matches(matchers, folding) => {
+                let prepared = Prepared::new(line, *folding);
+                let spans = matchers.iter().flat_map(|matcher| matcher.find_all(&prepared)).map(|span| (span, ())).collect();
+                first_spans(spans).into_iter().map(|(span, _)| text(span)).collect()
+            }
+            Extractor::Group(captures, index) => captures
+                .captures(line)
+                .iter()
+                .filter_map(|groups| groups.get(*index).copied().flatten())
+                .filter(|span| span.end > span.start)
+                .map(text)
+                .collect(),
+            Extractor::Replace(captures, template, true) => {
+                captures.captures(line).iter().map(|groups| captures.expand(template, groups, line)).collect()
+            }
+            Extractor::Replace(captures, template, false) => {
+                let mut replaced = String::with_capacity(line.len());
+                let mut end = 0;
+                for groups in captures.captures(line) {
+                    let Some(whole) = groups[0].filter(|whole| whole.start >= end) else {
+                        continue;
+                    };
+                    replaced.push_str(&line[end..whole.start]);
+                    replaced.push_str(&captures.expand(template, &groups, line));
+                    end = whole.end;
+                }
+                replaced.push_str(&line[end..]);
+                vec![replaced]
+            }
+        }
+    }
+}
+
+// Sorts spans by where they start, dropping empty ones and any that
+// overlap an earlier one.
+fn first_spans<T>(mut spans: Vec<(Span, T)>) -> Vec<(Span, T)> {
+    spans.retain(|(span, _)| span.end > span.start);
+    spans.sort_by_key(|(span, _)| span.start);
+    let mut end = 0;
+    spans.retain(|(span, _)| {
+        let keep = span.start >= end;
+        if keep {
+            end = span.end;
+        }
+        keep
+    });
+    spans
+}
+
+// Returns `text` in bold and the named color. The escape codes are
+// written directly, whatever `colored` has been told about the terminal,
+// so callers decide when to color with `colors_enabled`.
+#[cfg(feature = "cli")]
+fn paint_text(text: &str, color: &str) -> String {
+    let color: Color = color.parse().unwrap_or(Color::Red);
+    format!("\x1b[1;{}m{text}\x1b[0m", color.to_fg_str())
+}
+
+#[cfg(not(feature = "cli"))]
+fn paint_text(text: &str, _color: &str) -> String {
+    text.to_string()
+}
+
+// Returns true if output should be colored.
+// Without the `cli` feature there are no terminal colors.
+#[cfg(feature = "cli")]
+fn colors_enabled() -> bool {
+    colored::control::SHOULD_COLORIZE.should_colorize()
+}
+
+#[cfg(not(feature = "cli"))]
+fn colors_enabled() -> bool {
+    false
+}
+
+// Checks that a color name is one `highlight` understands.
+#[cfg(feature = "cli")]
+fn check_color(color: &str) -> Result<(), Box<dyn Error>> {
+    color.parse::<Color>().map_err(|_| format!("unknown color '{color}'"))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "cli"))]
+fn check_color(_color: &str) -> Result<(), Box<dyn Error>> {
+    Ok(())
+}
+
+// Returns a token that is cancelled when Ctrl-C is pressed.
+// The handler is installed on first use. Pressing Ctrl-C again, such as
+// while the results are printed, exits straight away.
+#[cfg(all(feature = "cli", unix))]
+fn interrupt_token() -> CancellationToken {
+    const SIGINT: i32 = 2;
+    static TOKEN: std::sync::OnceLock<CancellationToken> = std::sync::OnceLock::new();
+    extern "C" {
+        fn signal(signum: i32, handler: extern "C" fn(i32)) -> usize;
+        fn _exit(status: i32) -> !;
+    }
+    // Only touches atomics and calls `_exit`, so it is safe to run
+    // inside a signal handler.
+    extern "C" fn on_interrupt(_: i32) {
+        if let Some(token) = TOKEN.get() {
+            if token.is_cancelled() {
+                unsafe { _exit(INTERRUPTED_STATUS) }
+            }
+            token.cancel();
+        }
+    }
+    TOKEN
+        .get_or_init(|| {
+            unsafe { signal(SIGINT, on_interrupt) };
+            CancellationToken::new()
+        })
+        .clone()
+}
+
+#[cfg(not(all(feature = "cli", unix)))]
+fn interrupt_token() -> CancellationToken {
+    CancellationToken::new()
+}
+
+// Applies `--max-line-length` to a line.
+// Returns the part of the line to match, or `None` if it is skipped.
+fn limit_line<'l>(line: &'l str, args: &CommandArgs, stats: &mut Stats) -> Option<&'l str> {
+    match args.max_line_length {
+        Some(max) if line.len() > max && args.truncate_long_lines => {
+            stats.lines_truncated += 1;
+            let end = (0..=max).rev().find(|&i| line.is_char_boundary(i)).unwrap_or(0);
+            Some(&line[..end])
+        }
+        Some(max) if line.len() > max => {
+            stats.lines_skipped += 1;
+            None
+        }
+        _ => Some(line),
+    }
+}
+
+// Writes the matches from every committed version of a file as
+// `commit:path:line: text`.
+fn write_history(args: &CommandArgs, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+    let highlighter = Highlighter::new(args, colors_enabled())?;
+    for m in search_history(args)? {
+        let text = highlighter.highlight(&m.line);
+        writeln!(writer, "{}:{}:{}: {text}", m.commit, args.path.display(), printed_number(args, m.number))?;
+    }
+    Ok(())
+}
+
+// Formats up to 16 bytes as a `hexdump -C` row: the offset, the bytes
+// in hex and the printable ASCII ones.
+fn hexdump_row(offset: usize, chunk: &[u8]) -> String {
+    let mut hex = String::new();
+    for index in 0..16 {
+        if index == 8 {
+            hex.push(' ');
+        }
+        match chunk.get(index) {
+            Some(byte) => hex.push_str(&format!("{byte:02x} ")),
+            None => hex.push_str("   "),
+        }
+    }
+    let ascii: String = chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+    format!("{offset:08x}  {hex} |{ascii}|")
+}
+
+// Returns the character printed after a file name.
+fn name_terminator(args: &CommandArgs) -> char {
+    match args.null {
+        true => '\0',
+        false => '\n',
+    }
+}
+
+// Turns bytes read from a file into the text that is searched: one
+// character per byte with `binary` and UTF-8 otherwise, without ANSI
+// escape codes with `strip_ansi`.
+fn decode(args: &CommandArgs, bytes: Vec<u8>) -> Result<String, String> {
+    let text = match args.binary {
+        true => bytes.into_iter().map(char::from).collect(),
+        false => String::from_utf8(bytes).map_err(|_| "stream did not contain valid UTF-8")?,
+    };
+    match args.strip_ansi {
+        true => Ok(strip_ansi(&text)),
+        false => Ok(text),
+    }
+}
+
+// Returns a copy of `args` with its patterns in the form `--binary`
+// searches for: each byte of the pattern, or of a `\xNN` escape, becomes
+// the character with the same value. `\\` is a literal backslash.
+fn binary_patterns(args: &CommandArgs) -> Result<CommandArgs, String> {
+    let decode = |patterns: &[String]| patterns.iter().map(|p| decode_binary(p)).collect::<Result<Vec<_>, _>>();
+    Ok(CommandArgs {
+        query: decode_binary(&args.query)?,
+        any_of: decode(&args.any_of)?,
+        all_of: decode(&args.all_of)?,
+        none_of: decode(&args.none_of)?,
+        ..args.clone()
+    })
+}
+
+// Removes ANSI escape sequences: CSI sequences such as colors and
+// cursor movement, OSC sequences such as titles and hyperlinks, and
+// short escapes such as character set changes.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('[') => {
+                while chars.next().is_some_and(|c| !('\x40'..='\x7e').contains(&c)) {}
+            }
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || c == '\x1b' && chars.next_if_eq(&'\\').is_some() {
+                        break;
+                    }
+                }
+            }
+            Some(' '..='/') => {
+                while chars.next().is_some_and(|c| (' '..='/').contains(&c)) {}
+            }
+            _ => {}
+        }
+    }
+    stripped
+}
+
+// Decodes a single pattern for `binary_patterns`.
+fn decode_binary(pattern: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('x')) => {
+                let hex: String = chars.by_ref().skip(1).take(2).collect();
+                if hex.len() != 2 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(format!("invalid escape '\\x{hex}' in '{pattern}'"));
+                }
+                bytes.push(u8::from_str_radix(&hex, 16).unwrap());
+            }
+            ('\\', Some('\\')) => {
+                chars.next();
+                bytes.push(b'\\');
+            }
+            (c, _) => bytes.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    Ok(bytes.into_iter().map(char::from).collect())
+}
+
+// Reads the file, giving up at `deadline`.
+// Returns `None` if the deadline passes first. The read is left to
+// finish on its own thread, since a blocked read can't be interrupted.
+fn read_before(args: &CommandArgs, deadline: Option<Instant>) -> Result<Option<String>, Box<dyn Error>> {
+    let Some(deadline) = deadline else {
+        return args.read().map(Some);
+    };
+    let (sender, receiver) = mpsc::channel();
+    let reader = args.clone();
+    thread::spawn(move || sender.send(reader.read().map_err(|e| e.to_string())));
+    match receiver.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        Ok(contents) => Ok(Some(contents?)),
+        Err(_) => Ok(None),
+    }
+}
+
+// Prints a diagnostic about the file to stderr for `--verbose`.
+fn diagnose(args: &CommandArgs, message: impl fmt::Display) {
+    if args.verbose {
+        eprintln!("grepr: {}: {message}", args.path.display());
+    }
+}
+
+// Describes how the contents were read, for `--verbose`.
+fn describe_contents(args: &CommandArgs, contents: &str) -> String {
+    let mut description = match args.binary {
+        true => format!("read {} bytes as raw bytes", contents.chars().count()),
+        false => format!("read {} bytes as UTF-8", contents.len()),
+    };
+    if !args.binary && contents.starts_with('\u{feff}') {
+        description.push_str(" with a byte order mark");
+    }
+    if contents.contains("\r\n") {
+        description.push_str(", CRLF line endings");
+    }
+    if args.strip_ansi {
+        description.push_str(", ANSI escapes removed");
+    }
+    description
+}
+
+// Reports a file skipped by `--timeout` or `--file-timeout`.
+fn report_timeout(args: &CommandArgs) -> Result<(), Box<dyn Error>> {
+    if !args.no_messages {
+        let lang = args.lang();
+        eprintln!("grepr: {}: {}", args.path.display(), lang.text(Message::TimedOut));
+    }
+    Ok(())
+}
+
+// Parses a `--timeout` value in seconds, such as `30` or `0.5`.
+#[cfg(feature = "cli")]
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s.parse().map_err(|_| format!("invalid number of seconds '{s}'"))?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid number of seconds '{s}'"))
+}
+
+// Writes the statistics printed by `--stats`.
+fn write_stats(stats: &Stats, lang: Lang, writer: &mut impl std::io::Write) -> Result<(), Box<dyn Error>> {
+    writeln!(writer)?;
+    writeln!(writer, "{} {}", stats.lines_matched, lang.text(Message::MatchedLines))?;
+    writeln!(writer, "{} {}", stats.lines_searched, lang.text(Message::LinesSearched))?;
+    writeln!(writer, "{} {}", stats.bytes_searched, lang.text(Message::BytesSearched))?;
+    if stats.lines_skipped > 0 {
+        writeln!(writer, "{} {}", stats.lines_skipped, lang.text(Message::LongLinesSkipped))?;
+    }
+    if stats.lines_truncated > 0 {
+        writeln!(writer, "{} {}", stats.lines_truncated, lang.text(Message::LongLinesTruncated))?;
+    }
+    Ok(())
+}
+
+// Runs one stage of a search, reporting how long it took on stderr
+// when `enabled` is set.
+fn timed<T>(enabled: bool, stage: &str, f: impl FnOnce() -> T) -> T {
+    if !enabled {
+        return f();
+    }
+    let start = Instant::now();
+    let value = f();
+    eprintln!("timing: {stage} {:.3?}", start.elapsed());
+    value
+}
+
+// Expands a hyperlink format into a URL for a location in `path`.
+// Line and column are 1-based, as editors expect.
+fn hyperlink_url(format: &str, path: &Path, line: usize, column: usize) -> String {
+    let template = match format {
+        "file" => "file://{path}",
+        "vscode" => "vscode://file{path}:{line}:{column}",
+        template => template,
+    };
+    template
+        .replace("{path}", &percent_encode(&path.to_string_lossy()))
+        .replace("{line}", &line.to_string())
+        .replace("{column}", &column.to_string())
+}
+
+// Escapes a path for use in a URL, keeping `/` separators.
+fn percent_encode(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+// Quotes a CSV field if it contains a comma, quote or line break.
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// Escapes tabs, line breaks and backslashes in a TSV field.
+fn tsv_field(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+// Replaces each `{name}` in a template with `value(name)`.
+// `{{` and `}}` are literal braces. Returns an error for an unknown
+// placeholder or an unmatched brace.
+fn expand_template(template: &str, value: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(index) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..index]);
+        let (brace, after) = rest[index..].split_at(1);
+        if let Some(after) = after.strip_prefix(brace) {
+            expanded.push_str(brace);
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            return Err("unmatched '}' in format template".to_string());
+        }
+        let (name, after) = after.split_once('}').ok_or("unmatched '{' in format template")?;
+        let field = value(name).ok_or_else(|| format!("unknown placeholder '{{{name}}}' in format template"))?;
+        expanded.push_str(&field);
+        rest = after;
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+// Applies `--trim`, `--tabs` and control character escaping to a
+// matched line.
+// Returns the line to print and the span of the match within it.
+fn layout(m: &Match, args: &CommandArgs) -> (String, Option<Span>) {
+    let mut line = m.line;
+    let mut span = m.span;
+    if args.trim {
+        let trimmed = line.trim_start();
+        let offset = line.len() - trimmed.len();
+        line = trimmed;
+        span = span.map(|span| Span { start: span.start.saturating_sub(offset), end: span.end.saturating_sub(offset) });
+    }
+    let escape = |c: char| (c.is_control() || args.binary && !c.is_ascii()) && c != '\t' && !args.no_escape;
+    if args.tabs.is_none() && !line.contains(escape) {
+        return (line.to_string(), span);
+    }
+    let mut shown = String::with_capacity(line.len());
+    let mut new_span = span;
+    let mut column = 0;
+    for (index, c) in line.char_indices() {
+        if let (Some(span), Some(new_span)) = (span, new_span.as_mut()) {
+            if index == span.start {
+                new_span.start = shown.len();
+            }
+            if index == span.end {
+                new_span.end = shown.len();
+            }
+        }
+        let before = shown.len();
+        match (c, args.tabs) {
+            ('\t', Some(width)) => {
+                let stop = if width == 0 { 0 } else { width - column % width };
+                shown.extend(iter::repeat_n(' ', stop));
+            }
+            (c, _) if escape(c) && ((c as u32) < 0x80 || args.binary) => shown.push_str(&format!("\\x{:02x}", c as u32)),
+            (c, _) if escape(c) => shown.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            (c, _) => shown.push(c),
+        }
+        column += shown[before..].chars().count();
+    }
+    if let (Some(span), Some(new_span)) = (span, new_span.as_mut()) {
+        if span.end == line.len() {
+            new_span.end = shown.len();
+        }
+    }
+    (shown, new_span)
+}
+
+// Returns up to `width` characters of a long line, centered on the match.
+// An ellipsis marks each end where text was cut.
+fn preview(m: &Match, width: usize) -> String {
+    let chars: Vec<char> = m.line.chars().collect();
+    let (start, len) = match m.span {
+        Some(span) => (m.line[..span.start].chars().count(), m.line[span.start..span.end].chars().count()),
+        None => (0, 0),
+    };
+    let begin = start.saturating_sub(width.saturating_sub(len) / 2).min(chars.len().saturating_sub(width));
+    let end = (begin + width).min(chars.len());
+    let mut preview = String::new();
+    if begin > 0 {
+        preview.push('…');
+    }
+    preview.extend(&chars[begin..end]);
+    if end < chars.len() {
+        preview.push('…');
+    }
+    preview
+}
+
+// Returns the line number printed for the 0-based line `number`.
+fn printed_number(args: &CommandArgs, number: usize) -> usize {
+    number + args.line_number_start()
+}
+
+// Returns the value of a template placeholder for a match.
+fn template_field(name: &str, path: &str, m: &Match, args: &CommandArgs) -> Option<String> {
+    let value = match name {
+        "path" => path.to_string(),
+        "line" => printed_number(args, m.number).to_string(),
+        "column" => m.char_column().unwrap_or(0).to_string(),
+        "byte_column" => m.byte_column().unwrap_or(0).to_string(),
+        "match" => m.span.map_or("", |span| &m.line[span.start..span.end]).to_string(),
+        "text" => m.line.to_string(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+// Escapes text for a GitHub Actions workflow command.
+// Property values also need `:` and `,` escaped.
+fn github_escape(value: &str, property: bool) -> String {
+    let escaped = value.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A");
+    match property {
+        true => escaped.replace(':', "%3A").replace(',', "%2C"),
+        false => escaped,
+    }
+}
+
+// Quotes a string as a JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Wraps `text` in an OSC 8 terminal hyperlink to `url`.
+fn hyperlink(text: &str, url: &str) -> String {
+    format!("\x1b]8;;{url}\x1b\\{text}\x1b]8;;\x1b\\")
+}
+
+// Draws a progress bar for the current search on stderr.
+// The bar is cleared again once the last file has been searched.
+fn render_progress(progress: &Progress, total: usize) {
+    const WIDTH: usize = 40;
+    let mut stderr = std::io::stderr();
+    if progress.files_searched == progress.files_discovered {
+        let _ = write!(stderr, "\r{:width$}\r", "", width = WIDTH + 32);
+        return;
+    }
+    let percent = (progress.bytes_scanned * 100).checked_div(total).unwrap_or(100);
+    let filled = percent * WIDTH / 100;
+    let _ = write!(
+        stderr,
+        "\r[{}{}] {percent:>3}% {}/{} files, {} KiB",
+        "=".repeat(filled),
+        " ".repeat(WIDTH - filled),
+        progress.files_searched,
+        progress.files_discovered,
+        progress.bytes_scanned / 1024
+    );
+}
+
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn test_search_line_case_noinvert_good() {
+        let query = "this is a test.".to_string();
+        let path = PathBuf::new();
+        let contents = "this is a test.\nthis is another test!";
+        let ignore_case = false;
+        let invert_match = false;
+        let word = false;
+        let line = true;
+
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -308,8 +3491,28 @@ mod tests {
 
 
     #[test]
-    fn test_search_line_nocase_noinvert_bad() {
-        let query = "THIS is a test".to_string();
+    #[allow(clippy::needless_borrow)]
+    fn test_search_line_case_noinvert_bad() {
+        let query = "this is a test".to_string();
+        let path = PathBuf::new();
+        let contents = "this is a test.\nthis is another test!";
+        let ignore_case = false;
+        let invert_match = false;
+        let word = false;
+        let line = true;
+
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+
+        let mut search = Search::new(&contents);
+        let _ = search.find(&args);
+
+        assert_eq!(search.results.len(), 0)
+    }
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn test_search_line_nocase_noinvert_good() {
+        let query = "THIS is a test.".to_string();
         let path = PathBuf::new();
         let contents = "this is a test.\nthis is another test!";
         let ignore_case = true;
@@ -317,14 +3520,28 @@ mod tests {
         let word = false;
         let line = true;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+
+        let mut search = Search::new(&contents);
+        let _ = search.find(&args);
+
+        assert_eq!(search.results[0].1, "this is a test.")
+    }
+
+
+
+    #[test]
+    #[allow(clippy::needless_borrow)]
+    fn test_search_line_nocase_noinvert_bad() {
+        let query = "THIS is a test".to_string();
+        let path = PathBuf::new();
+        let contents = "this is a test.\nthis is another test!";
+        let ignore_case = true;
+        let invert_match = false;
+        let word = false;
+        let line = true;
+
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -333,6 +3550,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_line_nocase_invert_good() {
         let query = "THIS is a test.".to_string();
         let path = PathBuf::new();
@@ -342,14 +3560,7 @@ mod tests {
         let word = false;
         let line = true;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -360,6 +3571,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_line_nocase_invert_bad() {
         let query = "THIS is a test".to_string();
         let path = PathBuf::new();
@@ -369,14 +3581,7 @@ mod tests {
         let word = false;
         let line = true;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -385,6 +3590,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_word_case_noinvert_good() {
         let query = "another".to_string();
         let path = PathBuf::new();
@@ -394,14 +3600,7 @@ mod tests {
         let word = true;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -412,6 +3611,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_word_case_noinvert_bad() {
         let query = "nothing".to_string();
         let path = PathBuf::new();
@@ -421,14 +3621,7 @@ mod tests {
         let word = true;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -437,6 +3630,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_word_nocase_noinvert_good() {
         let query = "ANOTHER".to_string();
         let path = PathBuf::new();
@@ -446,14 +3640,7 @@ mod tests {
         let word = true;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -464,6 +3651,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_word_nocase_noinvert_bad() {
         let query = "NOTHING".to_string();
         let path = PathBuf::new();
@@ -473,14 +3661,7 @@ mod tests {
         let word = true;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -489,6 +3670,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_word_nocase_invert_good() {
         let query = "another".to_string();
         let path = PathBuf::new();
@@ -498,14 +3680,7 @@ mod tests {
         let word = true;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -516,6 +3691,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_word_nocase_invert_bad() {
         let query = "nothing".to_string();
         let path = PathBuf::new();
@@ -525,14 +3701,7 @@ mod tests {
         let word = true;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -543,6 +3712,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_partial_case_noinvert_good() {
         let query = "ano".to_string();
         let path = PathBuf::new();
@@ -552,14 +3722,7 @@ mod tests {
         let word = false;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -570,6 +3733,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_partial_case_noinvert_bad() {
         let query = "nothing".to_string();
         let path = PathBuf::new();
@@ -579,14 +3743,7 @@ mod tests {
         let word = false;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -595,6 +3752,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_partial_nocase_noinvert_good() {
         let query = "ANO".to_string();
         let path = PathBuf::new();
@@ -604,14 +3762,7 @@ mod tests {
         let word = false;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -622,6 +3773,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_partial_nocase_noinvert_bad() {
         let query = "NOTHING".to_string();
         let path = PathBuf::new();
@@ -631,14 +3783,7 @@ mod tests {
         let word = false;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -647,6 +3792,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_partial_nocase_invert_good() {
         let query = "ano".to_string();
         let path = PathBuf::new();
@@ -656,14 +3802,7 @@ mod tests {
         let word = false;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -674,6 +3813,7 @@ mod tests {
 
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_search_partial_nocase_invert_bad() {
         let query = "nothing".to_string();
         let path = PathBuf::new();
@@ -683,14 +3823,7 @@ mod tests {
         let word = false;
         let line = false;
 
-        let args = CommandArgs { 
-            query, 
-            path,
-            ignore_case,
-            invert_match,
-            word,
-            line 
-        };
+        let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
 
         let mut search = Search::new(&contents);
         let _ = search.find(&args);
@@ -698,4 +3831,462 @@ mod tests {
         assert_eq!(search.results.len(), 2)
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_line_range_parse() {
+        assert_eq!("5:".parse(), Ok(LineRange { start: Some(5), end: None }));
+        assert_eq!(":5".parse(), Ok(LineRange { start: None, end: Some(5) }));
+        assert!("5:1".parse::<LineRange>().is_err());
+        assert!("5".parse::<LineRange>().is_err())
+    }
+
+    #[test]
+    fn test_hyperlink_url_formats() {
+        let path = Path::new("/tmp/my notes.txt");
+
+        assert_eq!(hyperlink_url("file", path, 3, 1), "file:///tmp/my%20notes.txt");
+        assert_eq!(hyperlink_url("vscode", path, 3, 7), "vscode://file/tmp/my%20notes.txt:3:7");
+        assert_eq!(hyperlink_url("editor://{path}#{line}", path, 3, 7), "editor:///tmp/my%20notes.txt#3")
+    }
+
+    #[test]
+    fn test_write_csv_quoting() {
+        let args = CommandArgs::new("b".to_string(), PathBuf::from("a,b.txt"), false, false, false, false)
+            .with_format(OutputFormat::Csv);
+        let mut search = Search::new("say \"b\"");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "path,line,column,match,line_text\n\"a,b.txt\",1,6,b,\"say \"\"b\"\"\"\n")
+    }
+
+    #[test]
+    fn test_json_string_escapes() {
+        assert_eq!(json_string("a \"b\"\\\t\u{1}"), r#""a \"b\"\\\t\u0001""#)
+    }
+
+    #[test]
+    fn test_write_github_annotation() {
+        let args = CommandArgs::new("FIXME".to_string(), PathBuf::from("src/a,b.rs"), false, false, false, false)
+            .with_format(OutputFormat::Github);
+        let mut search = Search::new("ok\n// FIXME: 100%");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "::warning file=src/a%2Cb.rs,line=2,col=4,endColumn=9::// FIXME: 100%25\n")
+    }
+
+    #[test]
+    fn test_write_template() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::from("a.txt"), false, false, false, false)
+            .with_format_template(Some("{{{path}}}:{line}:{column} -> {match}".to_string()));
+        let mut search = Search::new("this is a test.");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "{a.txt}:1:11 -> test\n")
+    }
+
+    #[test]
+    fn test_write_template_unknown_placeholder() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_format_template(Some("{file}".to_string()));
+        let search = Search::new("");
+
+        assert!(search.write(&args, &mut Vec::new()).is_err())
+    }
+
+    #[test]
+    fn test_write_max_columns_preview() {
+        let contents = format!("{}needle{}", "a".repeat(50), "b".repeat(50));
+        let args = CommandArgs::new("needle".to_string(), PathBuf::new(), false, false, false, false)
+            .with_max_columns(Some(10), true);
+        let mut search = Search::new(&contents);
+        let _ = search.find(&args);
+
+        assert_eq!(preview(&search.matches[0], 10), "…aaneedlebb…")
+    }
+
+    #[test]
+    fn test_layout_trim_and_tabs() {
+        let args = CommandArgs::new("x".to_string(), PathBuf::new(), false, false, false, false)
+            .with_trim(true)
+            .with_tabs(Some(4));
+        let m = Match { number: 0, line: "\t  a\tx", span: Some(Span { start: 5, end: 6 }) };
+        let (line, span) = layout(&m, &args);
+
+        assert_eq!(line, "a   x");
+        assert_eq!(span, Some(Span { start: 4, end: 5 }))
+    }
+
+    #[test]
+    fn test_layout_escapes_control_characters() {
+        let args = CommandArgs::new("x".to_string(), PathBuf::new(), false, false, false, false);
+        let m = Match { number: 0, line: "\u{1b}[2Jx\u{85}", span: Some(Span { start: 4, end: 5 }) };
+        let (line, span) = layout(&m, &args);
+
+        assert_eq!(line, "\\x1b[2Jx\\u{85}");
+        assert_eq!(span, Some(Span { start: 7, end: 8 }))
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_highlight_patterns_in_order() {
+        let mut args = CommandArgs::new("warn".to_string(), PathBuf::new(), false, false, false, false)
+            .with_any_of(vec!["error".to_string()]);
+        args.match_color = Some("yellow".to_string());
+        let line = Highlighter::new(&args, true).unwrap().highlight("error then warn");
+
+        assert_eq!(line, "\x1b[1;32merror\x1b[0m then \x1b[1;33mwarn\x1b[0m");
+        assert_eq!(Highlighter::new(&args, false).unwrap().highlight("error then warn"), "error then warn")
+    }
+
+    #[test]
+    fn test_write_dedupe_count() {
+        let args = CommandArgs::new("".to_string(), PathBuf::from("a.log"), false, false, false, false)
+            .with_dedupe(true, true);
+        let mut search = Search::new("timeout\nok\ntimeout\ntimeout");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "a.log\n1: timeout (x3)\n2: ok\n")
+    }
+
+
+    #[test]
+    fn test_write_line_buffered() {
+        // Records each write that reaches the underlying writer.
+        struct Writes(Vec<Vec<u8>>);
+        impl std::io::Write for Writes {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.push(buf.to_vec());
+                Ok(buf.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let args = CommandArgs::new("b".to_string(), PathBuf::from("a.txt"), false, false, false, false)
+            .with_format(OutputFormat::Tsv);
+        let mut search = Search::new("b1\nb2");
+        let _ = search.find(&args);
+        let mut buffered = Writes(Vec::new());
+        search.write(&args, &mut buffered).unwrap();
+        let mut unbuffered = Writes(Vec::new());
+        search.write(&args.with_line_buffered(true), &mut unbuffered).unwrap();
+
+        assert_eq!(buffered.0.len(), 1);
+        assert_eq!(unbuffered.0.len(), 3);
+        assert_eq!(buffered.0.concat(), unbuffered.0.concat())
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds("0.5"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_seconds("30"), Ok(Duration::from_secs(30)));
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("soon").is_err())
+    }
+
+    #[test]
+    fn test_run_unreadable_file() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::from("does/not/exist.txt"), false, false, false, false)
+            .with_no_messages(true);
+
+        assert!(args.run().unwrap_err().is::<ReadErrors>())
+    }
+
+    #[test]
+    fn test_decode_binary_escapes() {
+        assert_eq!(decode_binary(r"\xDE\xad!"), Ok("\u{de}\u{ad}!".to_string()));
+        assert_eq!(decode_binary(r"caf\\xé"), Ok("caf\\x\u{c3}\u{a9}".to_string()));
+        assert!(decode_binary(r"\xZ1").is_err());
+        assert!(decode_binary(r"\x1").is_err())
+    }
+
+    #[test]
+    fn test_write_emacs() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::from("src/a.rs"), false, false, false, false)
+            .with_format(OutputFormat::Emacs);
+        let mut search = Search::new("ok\nä test");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "src/a.rs:2:3: ä test\n")
+    }
+
+    #[test]
+    fn test_write_null_header() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::from("my notes.txt"), false, false, false, false)
+            .with_null(true);
+        let mut search = Search::new("test");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert!(String::from_utf8(output).unwrap().starts_with("my notes.txt\0"))
+    }
+
+    #[test]
+    #[cfg(all(feature = "serde", feature = "cli"))]
+    fn test_match_serde_round_trip() {
+        let m = Match { number: 2, line: "a test", span: Some(Span { start: 2, end: 6 }) };
+        let text = toml::to_string(&m).unwrap();
+
+        assert_eq!(toml::from_str::<Match>(&text).unwrap(), m)
+    }
+
+    #[test]
+    fn test_write_fields() {
+        let args = CommandArgs::new("GET".to_string(), PathBuf::new(), false, false, false, false)
+            .with_field(Some(3), None);
+        let mut search = Search::new("10.0.0.1 GET  /index.html 200\n10.0.0.2 POST /login 302\n10.0.0.3 GET");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+        let args = args.with_field(Some(2), Some(".".to_string()));
+        let mut delimited = Vec::new();
+        search.write(&args, &mut delimited).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "/index.html\n\n");
+        assert_eq!(String::from_utf8(delimited).unwrap(), "0\n0\n")
+    }
+
+    #[test]
+    fn test_write_show_function() {
+        let args = CommandArgs::new("todo".to_string(), PathBuf::from("main.py"), false, false, false, false)
+            .with_show_function(true);
+        let contents = "def load():\n    todo(1)\n    todo(2)\n\nclass Cache:\n    todo(3)";
+        let mut search = Search::new(contents);
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().filter(|line| line.contains('=')).collect();
+
+        assert_eq!(lines, vec!["1= def load():", "5= class Cache:"])
+    }
+
+    #[test]
+    fn test_write_md_scope_sections() {
+        let args = CommandArgs::new("grepr".to_string(), PathBuf::from("README.md"), false, false, false, false)
+            .with_md_scope(Some(MdScope::Code));
+        let contents = "# grepr\n## Install\n```sh\n# build grepr\ncargo install grepr\n```\nrun grepr";
+        let mut search = Search::new(contents);
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().skip(1).collect();
+
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "2= ## Install")
+    }
+
+    #[test]
+    fn test_write_hexdump() {
+        let args = CommandArgs::new("\u{de}\u{ad}".to_string(), PathBuf::new(), false, false, false, false)
+            .with_binary(true)
+            .with_hex_context(Some(2));
+        let contents: String = b"\x7fELF\n\x00\x01\xde\xad\xbe\xef".iter().map(|&b| char::from(b)).collect();
+        let mut search = Search::new(&contents);
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        let expected = "2: match at 0x00000007\n00000005  00 01 de ad be ef                                 |......|\n";
+        assert_eq!(String::from_utf8(output).unwrap(), expected)
+    }
+
+    #[test]
+    fn test_write_stats_lang() {
+        let stats = Stats { lines_matched: 2, lines_searched: 10, bytes_searched: 80, ..Stats::default() };
+        let mut output = Vec::new();
+        write_stats(&stats, Lang::De, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "\n2 übereinstimmende Zeilen\n10 durchsuchte Zeilen\n80 durchsuchte Bytes\n")
+    }
+
+    #[test]
+    fn test_skips_path() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::from("tests/fixtures/a.txt"), true, true, false, false)
+            .with_invert_file_match(Some("FIXTURES".to_string()));
+
+        assert!(args.skips_path());
+        assert!(!args.with_invert_file_match(Some("src/".to_string())).skips_path())
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        let text = "\x1b[1;31merror\x1b[0m: \x1b]8;;https://x\x1b\\disk\x1b]8;;\x07 full\x1b(B";
+
+        assert_eq!(strip_ansi(text), "error: disk full")
+    }
+
+    #[test]
+    fn test_lang_deterministic() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false)
+            .with_deterministic(true);
+
+        assert_eq!(args.lang(), Lang::En);
+        assert_eq!(args.with_lang(Some(Lang::Fr)).lang(), Lang::Fr)
+    }
+
+    #[test]
+    fn test_write_line_number_start() {
+        let args = CommandArgs::new("test".to_string(), PathBuf::from("src/a.rs"), false, false, false, false)
+            .with_format(OutputFormat::Emacs)
+            .with_line_number_start(100);
+        let mut search = Search::new("ok\nä test");
+        let _ = search.find(&args);
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+
+        assert_eq!(String::from_utf8(output).unwrap(), "src/a.rs:101:3: ä test\n")
+    }
+
+    #[test]
+    fn test_context_at_edges() {
+        let args = CommandArgs::new("x".to_string(), PathBuf::new(), false, false, false, false);
+        let mut search = Search::new("x\r\nmid\r\nx");
+        let _ = search.find(&args);
+        let first = search.context(&search.get_matches()[0], 3, 1);
+        let last = search.context(&search.get_matches()[1], 1, 3);
+
+        assert_eq!((first.before, first.after), (vec![], vec![Line { number: 1, text: "mid" }]));
+        assert_eq!((last.before, last.after), (vec![Line { number: 1, text: "mid" }], vec![]))
+    }
+
+    #[test]
+    fn test_find_page_matches_find() {
+        let contents = "a test\r\nnone\n```\ntest one\n```\ntest two\n```\ntest three\n";
+        let base = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        let ranged = base.clone().with_line_range(Some(LineRange { start: Some(1), end: Some(5) }));
+        let scoped = base.clone().with_md_scope(Some(MdScope::Code));
+        for args in [base, ranged, scoped] {
+            let mut search = Search::new(contents);
+            search.find(&args).unwrap();
+            let mut paged = Vec::new();
+            let mut cursor = Some(Cursor::default());
+            while let Some(next) = cursor {
+                let page = search.find_page(&args, next, 1).unwrap();
+                assert!(page.matches.len() <= 1);
+                paged.extend(page.matches);
+                cursor = page.next;
+            }
+
+            assert_eq!(&paged, search.get_matches())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_highlight_line_modes() {
+        let args = |invert_match, word, line| CommandArgs::new("test".to_string(), PathBuf::new(), false, invert_match, word, line);
+        let highlight = |args: &CommandArgs, line: &str| Highlighter::new(args, true).unwrap().highlight(line);
+
+        assert_eq!(highlight(&args(false, true, false), "testing a test"), "testing a \x1b[1;31mtest\x1b[0m");
+        assert_eq!(highlight(&args(false, false, true), "test"), "\x1b[1;31mtest\x1b[0m");
+        assert_eq!(highlight(&args(true, false, false), "a test"), "a test")
+    }
+
+    #[test]
+    #[cfg(feature = "cli")]
+    fn test_highlight_matcher_spans() {
+        let highlight = |args: &CommandArgs, line: &str| Highlighter::new(args, true).unwrap().highlight(line);
+        let ignore_case = CommandArgs::new("test".to_string(), PathBuf::new(), true, false, false, false);
+        let regex = CommandArgs::new(r"\d+".to_string(), PathBuf::new(), false, false, false, false)
+            .with_engine(Engine::Regex);
+        let accents = CommandArgs::new("resume".to_string(), PathBuf::new(), false, false, false, false)
+            .with_ignore_accents(true);
+
+        assert_eq!(highlight(&ignore_case, "a TEST"), "a \x1b[1;31mTEST\x1b[0m");
+        assert_eq!(highlight(&regex, "v12 and 3"), "v\x1b[1;31m12\x1b[0m and \x1b[1;31m3\x1b[0m");
+        assert_eq!(highlight(&accents, "my résumé"), "my \x1b[1;31mrésumé\x1b[0m")
+    }
+
+    #[test]
+    fn test_describe_contents() {
+        let args = CommandArgs::new("a".to_string(), PathBuf::new(), false, false, false, false);
+
+        assert_eq!(describe_contents(&args, "\u{feff}é\r\n"), "read 7 bytes as UTF-8 with a byte order mark, CRLF line endings");
+        assert_eq!(describe_contents(&args.with_binary(true), "\u{e9}\n"), "read 2 bytes as raw bytes")
+    }
+
+    #[test]
+    fn test_inline_case_flags() {
+        let contents = "Foo\nfoo\nFOO";
+        let args = |query: &str| CommandArgs::new(query.to_string(), PathBuf::new(), true, false, false, false)
+            .with_engine(Engine::Regex);
+        let numbers = |args: &CommandArgs| search_str(contents, args).unwrap().into_iter().map(|m| m.0).collect::<Vec<_>>();
+
+        assert_eq!(numbers(&args("(?-i)Foo")), vec![0]);
+        assert_eq!(numbers(&args("(?-i:F)oo")), vec![0, 2]);
+        assert_eq!(numbers(&args("(?i)foo").with_case_sensitive(true)), vec![0, 1, 2]);
+        assert_eq!(numbers(&args("foo").with_case_sensitive(true)), vec![1])
+    }
+
+    #[test]
+    fn test_write_only_matching_and_replace() {
+        let contents = "ann@example.com, bob@test.org\nno address";
+        let query = r"(?<user>\w+)@(\w+)\.\w+";
+        let args = CommandArgs::new(query.to_string(), PathBuf::from("a.txt"), false, false, false, false)
+            .with_engine(Engine::Regex);
+        let output = |args: &CommandArgs| {
+            let mut search = Search::new(contents);
+            search.find(args).unwrap();
+            let mut output = Vec::new();
+            search.write(args, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(output(&args.clone().with_only_matching(true)), "a.txt\n1: ann@example.com\n1: bob@test.org\n");
+        assert_eq!(output(&args.clone().with_only_matching(true).with_group(Some("user".to_string()))), "a.txt\n1: ann\n1: bob\n");
+        assert_eq!(output(&args.clone().with_replace(Some("$2/${user}".to_string()))), "a.txt\n1: example/ann, test/bob\n");
+        assert_eq!(output(&args.with_only_matching(true).with_replace(Some("<$1>".to_string()))), "a.txt\n1: <ann>\n1: <bob>\n")
+    }
+
+    #[test]
+    fn test_write_context_separators() {
+        let contents = "a\nmatch 1\nb\nc\nd\ne\nmatch 2\nmatch 3\nf";
+        let args = CommandArgs::new("match".to_string(), PathBuf::from("a.txt"), false, false, false, false)
+            .with_context(1, 1);
+        let output = |args: &CommandArgs| {
+            let mut search = Search::new(contents);
+            search.find(args).unwrap();
+            let mut output = Vec::new();
+            search.write(args, &mut output).unwrap();
+            String::from_utf8(output).unwrap()
+        };
+
+        assert_eq!(output(&args), "a.txt\n1- a\n2: match 1\n3- b\n--\n6- e\n7: match 2\n8: match 3\n9- f\n");
+        assert_eq!(
+            output(&args.clone().with_context_separator(Some("==".to_string()))),
+            "a.txt\n1- a\n2: match 1\n3- b\n==\n6- e\n7: match 2\n8: match 3\n9- f\n"
+        );
+        assert_eq!(output(&args.with_context_separator(None)), "a.txt\n1- a\n2: match 1\n3- b\n6- e\n7: match 2\n8: match 3\n9- f\n")
+    }
+
+    #[test]
+    fn test_write_diff() {
+        let contents = "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\neleven\ntwelve";
+        let args = CommandArgs::new("(t)(wo|en)".to_string(), PathBuf::from("a.txt"), false, false, true, false)
+            .with_engine(Engine::Regex)
+            .with_replace(Some("$2$1".to_string()))
+            .with_diff(true);
+        let mut search = Search::new(contents);
+        search.find(&args).unwrap();
+        let mut output = Vec::new();
+        search.write(&args, &mut output).unwrap();
+        let expected = "--- a.txt\n+++ a.txt\n\
+            @@ -1,5 +1,5 @@\n one\n-two\n+wot\n three\n four\n five\n\
+            @@ -7,6 +7,6 @@\n seven\n eight\n nine\n-ten\n+ent\n eleven\n twelve\n";
+
+        assert_eq!(String::from_utf8(output).unwrap(), expected)
+    }
+}
@@ -1,13 +1,194 @@
-use clap::Parser;
+use clap::builder::ArgPredicate;
+use clap::{value_parser, Arg, ArgAction, Command, CommandFactory, FromArgMatches};
+use clap_complete::{generate, Shell};
+use colored::*;
 use grepr::*;
+use grepr::config::{self, with_env_opts, Config};
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
 use std::process;
 
 
+// The status a process killed by SIGPIPE exits with in the shell.
+const BROKEN_PIPE_STATUS: i32 = 128 + 13;
+
+// The example snippets printed by `--examples`, with their titles.
+// These are the same files included in the crate documentation.
+const EXAMPLES: [(&str, &str); 3] = [
+    ("A simple search", include_str!("../examples/simple.md")),
+    ("Search for an exact word", include_str!("../examples/exact_word.md")),
+    ("Lines without a match", include_str!("../examples/invert.md")),
+];
+
+// Flags handled by the binary that can't be combined with any other
+// argument, so GREPR_OPTS is left out when one of them is given.
+const EXCLUSIVE_FLAGS: [&str; 4] = ["--generate-completions", "--examples", "--capabilities", "--check-config"];
+
 fn main() {
-    let args = CommandArgs::parse();
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let argv = match has_exclusive_flag(&argv) {
+        true => argv,
+        false => with_env_opts(argv).unwrap_or_else(|e| {
+            println!("{}: {e}", Lang::from_env().application_error());
+            process::exit(1);
+        }),
+    };
+    let matches = command().get_matches_from(argv);
+
+    if let Some(shell) = matches.get_one::<Shell>("generate_completions") {
+        generate(*shell, &mut command(), "grepr", &mut std::io::stdout());
+        return;
+    }
+    if matches.get_flag("capabilities") {
+        print_capabilities();
+        return;
+    }
+    if matches.get_flag("check_config") {
+        if let Err(e) = check_config() {
+            println!("{}: {e}", Lang::from_env().application_error());
+            process::exit(1);
+        }
+        return;
+    }
+    if matches.get_flag("examples") {
+        print_examples();
+        return;
+    }
 
-    if let Err(e) = args.run() {
-        println!("Application error: {e}");
+    let mut args = CommandArgs::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    if let Err(e) = run(&mut args) {
+        if is_broken_pipe(e.as_ref()) {
+            process::exit(BROKEN_PIPE_STATUS);
+        }
+        if e.is::<Cancelled>() {
+            process::exit(INTERRUPTED_STATUS);
+        }
+        if e.is::<ReadErrors>() {
+            process::exit(ERROR_STATUS);
+        }
+        println!("{}: {e}", args.lang().application_error());
         process::exit(1);
     }
-}
\ No newline at end of file
+}
+
+// Resolves the final options and runs the search.
+// Options from the config file only apply where the command line is silent.
+fn run(args: &mut CommandArgs) -> Result<(), Box<dyn Error>> {
+    let config = Config::load()?.unwrap_or_default();
+    config.apply(args)?;
+    args.run()
+}
+
+// Returns true if the command line, before GREPR_OPTS is added, has one
+// of `EXCLUSIVE_FLAGS`. Arguments after `--` are never flags.
+fn has_exclusive_flag(argv: &[OsString]) -> bool {
+    argv.iter()
+        .skip(1)
+        .filter_map(|arg| arg.to_str())
+        .take_while(|&arg| arg != "--")
+        .any(|arg| EXCLUSIVE_FLAGS.iter().any(|&flag| arg.split_once('=').map_or(arg, |(name, _)| name) == flag))
+}
+
+// Returns true if the error is a write to a closed pipe, such as when
+// the output is piped into `head`.
+fn is_broken_pipe(e: &(dyn Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe)
+}
+
+// Prints every problem found in the configuration file.
+// Returns an error if there were any, so the exit status shows it.
+fn check_config() -> Result<(), Box<dyn Error>> {
+    let Some(path) = config::path() else {
+        println!("no config file found");
+        return Ok(());
+    };
+    let contents = fs::read_to_string(&path).map_err(|e| format!("could not read config file {}: {e}", path.display()))?;
+    let problems = config::check(&contents).map_err(|e| format!("{}: {e}", path.display()))?;
+    for problem in &problems {
+        println!("{}: {problem}", path.display());
+    }
+    match problems.len() {
+        0 => Ok(()),
+        count => Err(format!("{count} problems found in {}", path.display()).into()),
+    }
+}
+
+// Prints the example snippets grouped by title, with the commands
+// highlighted and the Markdown fences removed.
+fn print_examples() {
+    for (title, snippet) in EXAMPLES {
+        println!("{}", title.bold());
+        let body = snippet.trim_end().trim_end_matches('`').trim_end();
+        for line in body.lines().filter(|line| !line.starts_with("```")) {
+            match line.strip_prefix("$ ") {
+                Some(command) => println!("  {} {}", "$".dimmed(), command.green()),
+                None if line.is_empty() => println!(),
+                None => println!("  {line}"),
+            }
+        }
+        println!();
+    }
+}
+
+// Prints the version and the optional features this binary was built
+// with as a JSON object, for tools that wrap grepr.
+fn print_capabilities() {
+    let features = [
+        ("async", cfg!(feature = "async")),
+        ("fancy", cfg!(feature = "fancy")),
+        ("ffi", cfg!(feature = "ffi")),
+        ("pcre2", cfg!(feature = "pcre2")),
+        ("serde", cfg!(feature = "serde")),
+    ];
+    let features: Vec<String> = features.iter().map(|(name, enabled)| format!("\"{name}\":{enabled}")).collect();
+    println!("{{\"version\":\"{}\",\"features\":{{{}}}}}", env!("CARGO_PKG_VERSION"), features.join(","));
+}
+
+// Builds the full command line interface.
+// Adds the flags that are handled by the binary rather than `CommandArgs`.
+// `-P` is shorthand for `--engine pcre2`, as in grep.
+fn command() -> Command {
+    CommandArgs::command()
+    .mut_arg("engine", |arg| arg.default_value_if("pcre2", ArgPredicate::IsPresent, "pcre2"))
+    .arg(
+        Arg::new("pcre2")
+            .short('P')
+            .long("pcre2")
+            .action(ArgAction::SetTrue)
+            .help("Uses Perl-compatible regular expressions, like --engine pcre2")
+            .conflicts_with_all(["engine", "fuzzy", "basic_regexp", "extended_regexp"]),
+    )
+    .arg(
+        Arg::new("generate_completions")
+            .long("generate-completions")
+            .value_name("SHELL")
+            .value_parser(value_parser!(Shell))
+            .help("Prints a shell completion script")
+            .exclusive(true)
+            .hide(true),
+    )
+    .arg(
+        Arg::new("examples")
+            .long("examples")
+            .action(ArgAction::SetTrue)
+            .help("Prints example searches")
+            .exclusive(true),
+    )
+    .arg(
+        Arg::new("capabilities")
+            .long("capabilities")
+            .action(ArgAction::SetTrue)
+            .help("Prints the version and optional features as JSON")
+            .exclusive(true),
+    )
+    .arg(
+        Arg::new("check_config")
+            .long("check-config")
+            .action(ArgAction::SetTrue)
+            .help("Checks the config file for unknown keys and bad values")
+            .exclusive(true),
+    )
+}
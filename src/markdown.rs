@@ -0,0 +1,89 @@
+//! Restricting a search to one part of Markdown documents.
+//!
+//! Used for `--md-scope`. Lines are classified in order as they are
+//! read. Fenced code blocks, opened and closed by three or more backticks
+//! or tildes, are code along with their fences. ATX headings (`#` to
+//! `######`) are headings, and every other line is prose.
+use crate::{CommandArgs, MdScope};
+
+
+// The `--md-scope` filter, along with the fence of the code block the
+// last line was in.
+#[derive(Clone)]
+pub(crate) struct ScopeFilter {
+    scope: MdScope,
+    fence: Option<(char, usize)>,
+}
+
+impl ScopeFilter {
+    // Builds the filter requested by `args`.
+    // Returns `None` if `--md-scope` was not given.
+    pub(crate) fn new(args: &CommandArgs) -> Option<ScopeFilter> {
+        args.md_scope.map(|scope| ScopeFilter { scope, fence: None })
+    }
+
+    // Builds a filter that accepts only headings, for finding the
+    // section titles of a document.
+    pub(crate) fn headings() -> ScopeFilter {
+        ScopeFilter { scope: MdScope::Headings, fence: None }
+    }
+
+    // Returns true if `line` is in the requested part of the document.
+    // Lines are expected in order, since a fence changes every line up
+    // to the closing one.
+    pub(crate) fn accepts(&mut self, line: &str) -> bool {
+        self.classify(line) == self.scope
+    }
+
+    // Returns the part of the document `line` is in, and notes any
+    // fence it opens or closes.
+    fn classify(&mut self, line: &str) -> MdScope {
+        let trimmed = line.trim_start();
+        let marker = trimmed.chars().next().filter(|&c| c == '`' || c == '~');
+        if let Some(marker) = marker {
+            let length = trimmed.chars().take_while(|&c| c == marker).count();
+            match self.fence {
+                None if length >= 3 => {
+                    self.fence = Some((marker, length));
+                    return MdScope::Code;
+                }
+                Some((open, open_length)) if open == marker && length >= open_length && trimmed[length..].trim().is_empty() => {
+                    self.fence = None;
+                    return MdScope::Code;
+                }
+                _ => {}
+            }
+        }
+        if self.fence.is_some() {
+            return MdScope::Code;
+        }
+        let hashes = trimmed.chars().take_while(|&c| c == '#').count();
+        match (1..=6).contains(&hashes) && trimmed[hashes..].chars().next().is_none_or(char::is_whitespace) {
+            true => MdScope::Headings,
+            false => MdScope::Prose,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_fences() {
+        let mut filter = ScopeFilter { scope: MdScope::Code, fence: None };
+        let lines = ["# Title", "text", "````rust", "```", "# not a heading", "````", "#hashtag"];
+        let kinds: Vec<MdScope> = lines.iter().map(|line| filter.classify(line)).collect();
+
+        assert_eq!(kinds, vec![
+            MdScope::Headings,
+            MdScope::Prose,
+            MdScope::Code,
+            MdScope::Code,
+            MdScope::Code,
+            MdScope::Code,
+            MdScope::Prose,
+        ])
+    }
+}
@@ -0,0 +1,1160 @@
+//! Line preparation and match finding used by `Search`.
+//!
+//! Lines are prepared once (for example by folding case) and matched as
+//! bytes. Each prepared line remembers where its bytes came from, so a
+//! match found in the prepared bytes always maps back to a span on
+//! character boundaries of the original line.
+use std::borrow::Cow;
+use std::iter;
+use std::sync::OnceLock;
+use aho_corasick::{AhoCorasick, MatchKind};
+use memchr::memmem::Finder;
+use regex::bytes::Regex;
+use unicode_normalization::char::{canonical_combining_class, is_combining_mark};
+use unicode_normalization::UnicodeNormalization;
+use crate::{posix, CommandArgs, Engine, Normalization, Span};
+
+
+// How lines and queries are transformed before matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Folding {
+    case: bool,
+    normalize: Option<Normalization>,
+    accents: bool,
+}
+
+impl Folding {
+    // Returns the transformations requested by `args`. Case is only
+    // folded for literal queries. Regular expressions ignore case with
+    // `(?i)` instead, so inline flags such as `(?-i)` keep working.
+    pub(crate) fn new(args: &CommandArgs) -> Folding {
+        let case = args.ignore_case && engine(args) == Engine::Literal;
+        Folding { case, normalize: args.normalize, accents: args.ignore_accents }
+    }
+
+    // Returns true if lines can be matched as they are.
+    fn is_identity(&self) -> bool {
+        !self.case && self.normalize.is_none() && !self.accents
+    }
+
+    // Returns true if only case is folded. For ASCII text that is a plain
+    // byte-for-byte lowercase, which keeps every offset the same.
+    fn is_case_only(&self) -> bool {
+        self.case && self.normalize.is_none() && !self.accents
+    }
+}
+
+// A line prepared for matching.
+// When the bytes differ from the original line, `sources` holds the byte
+// range in the original of the character each prepared byte came from.
+// An empty `sources` means each prepared byte is at the same offset as
+// in the original line.
+pub(crate) struct Prepared<'a> {
+    bytes: Cow<'a, [u8]>,
+    sources: Cow<'a, [(usize, usize)]>,
+    len: usize,
+}
+
+// Reusable storage for prepared lines, so preparing each line of a
+// search doesn't allocate once the buffers have grown.
+#[derive(Default)]
+pub(crate) struct LineBuffer {
+    bytes: Vec<u8>,
+    sources: Vec<(usize, usize)>,
+}
+
+impl LineBuffer {
+    // Prepares a line into this buffer.
+    pub(crate) fn prepare<'a>(&'a mut self, line: &'a str, folding: Folding) -> Prepared<'a> {
+        if folding.is_identity() {
+            return Prepared::new(line, folding);
+        }
+        self.bytes.clear();
+        self.sources.clear();
+        if folding.is_case_only() && line.is_ascii() {
+            self.bytes.extend(line.bytes().map(|b| b.to_ascii_lowercase()));
+            return Prepared { bytes: Cow::Borrowed(&self.bytes), sources: Cow::Borrowed(&[]), len: line.len() };
+        }
+        fold(line, folding, &mut self.bytes, &mut self.sources);
+        Prepared { bytes: Cow::Borrowed(&self.bytes), sources: Cow::Borrowed(&self.sources), len: line.len() }
+    }
+}
+
+impl<'a> Prepared<'a> {
+    // Prepares a line by applying each transformation in `folding`.
+    pub(crate) fn new(line: &'a str, folding: Folding) -> Prepared<'a> {
+        if folding.is_identity() {
+            return Prepared { bytes: Cow::Borrowed(line.as_bytes()), sources: Cow::Borrowed(&[]), len: line.len() };
+        }
+        if folding.is_case_only() && line.is_ascii() {
+            let bytes = line.as_bytes().to_ascii_lowercase();
+            return Prepared { bytes: Cow::Owned(bytes), sources: Cow::Borrowed(&[]), len: line.len() };
+        }
+        let mut bytes = Vec::with_capacity(line.len());
+        let mut sources = Vec::with_capacity(line.len());
+        fold(line, folding, &mut bytes, &mut sources);
+        Prepared { bytes: Cow::Owned(bytes), sources: Cow::Owned(sources), len: line.len() }
+    }
+
+    // Returns the prepared bytes.
+    pub(crate) fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    // Maps a range of prepared bytes back onto the original line.
+    // The result is widened to whole characters of the original.
+    pub(crate) fn span(&self, start: usize, end: usize) -> Span {
+        if self.sources.is_empty() {
+            return Span { start, end };
+        }
+        let original_start = self.sources.get(start).map_or(self.len, |source| source.0);
+        let original_end = if end > start { self.sources[end - 1].1 } else { original_start };
+        Span { start: original_start, end: original_end }
+    }
+}
+
+// Appends the folded bytes of `line` to `bytes`, and the source range of
+// each byte to `sources`.
+fn fold(line: &str, folding: Folding, bytes: &mut Vec<u8>, sources: &mut Vec<(usize, usize)>) {
+    let mut buf = [0; 4];
+    let mut push = |c: char, source: (usize, usize)| {
+        let mut push_one = |c: char| {
+            let encoded = c.encode_utf8(&mut buf).as_bytes();
+            bytes.extend_from_slice(encoded);
+            sources.extend(iter::repeat_n(source, encoded.len()));
+        };
+        if folding.case {
+            c.to_lowercase().for_each(&mut push_one);
+        } else {
+            push_one(c);
+        }
+    };
+    if folding.normalize.is_none() && !folding.accents {
+        for (start, c) in line.char_indices() {
+            push(c, (start, start + c.len_utf8()));
+        }
+    } else {
+        for (start, end) in segments(line) {
+            let segment = &line[start..end];
+            let chars: Box<dyn Iterator<Item = char>> = match (folding.accents, folding.normalize) {
+                (true, Some(Normalization::Nfkc)) => Box::new(strip_accents(segment.nfkd())),
+                (true, _) => Box::new(strip_accents(segment.nfd())),
+                (false, Some(Normalization::Nfkc)) => Box::new(segment.nfkc()),
+                (false, _) => Box::new(segment.nfc()),
+            };
+            chars.for_each(|c| push(c, (start, end)));
+        }
+    }
+}
+
+// Finds matches of a query within prepared lines.
+pub(crate) trait Matcher: Send + Sync {
+    // Finds the first match in a line.
+    // Returns `None` if the line does not match, ignoring `invert_match`.
+    fn find(&self, line: &Prepared) -> Option<Span>;
+
+    // Finds every non-overlapping match in a line, in order.
+    fn find_all(&self, line: &Prepared) -> Vec<Span> {
+        self.find(line).into_iter().collect()
+    }
+}
+
+// Builds the matcher for the query and options in `args`.
+// Extra patterns from `--any-of`, `--all-of` and `--none-of` are combined
+// with the query into a single matcher, so each line is checked once.
+pub(crate) fn new_matcher(args: &CommandArgs) -> Result<Box<dyn Matcher>, String> {
+    if args.any_of.is_empty() && args.all_of.is_empty() && args.none_of.is_empty() && args.near.is_none() {
+        return pattern_matcher(&args.query, args);
+    }
+    let matchers = |patterns: &[String]| patterns.iter().map(|p| pattern_matcher(p, args)).collect::<Result<Vec<_>, _>>();
+    let mut all: Vec<Box<dyn Matcher>> = matchers(&args.all_of)?;
+    if let Some(near) = &args.near {
+        let folding = Folding::new(args);
+        // Regular expressions leave case to `(?i)`, so the terms have to
+        // fold it themselves.
+        let fold_case = args.ignore_case && !folding.case;
+        let term = |term: &str| fold_word(Prepared::new(term, folding).bytes(), fold_case).into_owned();
+        all.push(Box::new(NearMatcher {
+            first: term(&near.first),
+            second: term(&near.second),
+            distance: near.distance,
+            whole: args.word,
+            fold_case,
+        }));
+    }
+    let literal_set = engine(args) == Engine::Literal && args.fuzzy.is_none() && !args.word && !args.line;
+    let any: Vec<Box<dyn Matcher>> = match literal_set && !args.any_of.is_empty() {
+        true => vec![Box::new(LiteralSetMatcher::new(args)?)],
+        false => pattern_matchers(args)?,
+    };
+    Ok(Box::new(BooleanMatcher {
+        any,
+        all,
+        none: matchers(&args.none_of)?,
+    }))
+}
+
+// Returns the query if `args` asks for nothing but a plain substring
+// search, so the whole contents can be scanned for it at once instead of
+// line by line. Queries containing line breaks are excluded, as they
+// can never match within a single line.
+pub(crate) fn literal_query(args: &CommandArgs) -> Option<&[u8]> {
+    let plain = !args.ignore_case && args.normalize.is_none() && !args.ignore_accents
+        && !args.word && !args.line && !args.invert_match && args.fuzzy.is_none()
+        && args.any_of.is_empty() && args.all_of.is_empty() && args.none_of.is_empty() && args.near.is_none()
+        && args.line_range.is_none() && args.since.is_none() && args.until.is_none()
+        && args.max_line_length.is_none() && args.md_scope.is_none() && engine(args) == Engine::Literal;
+    let query = args.query.as_bytes();
+    (plain && !query.is_empty() && !query.contains(&b'\n') && !query.contains(&b'\r')).then_some(query)
+}
+
+// Describes how `args` will be matched, one `name: value` line per
+// step, for `--explain`.
+pub(crate) fn explain(args: &CommandArgs) -> Vec<String> {
+    let folding = Folding::new(args);
+    let prepared = |pattern: &str| format!("{:?}", String::from_utf8_lossy(Prepared::new(pattern, folding).bytes()));
+    let compiled = |pattern: &str| match engine(args) {
+        Engine::Literal => prepared(pattern),
+        _ => format!("{:?}", regex_pattern(pattern, args)),
+    };
+    let mut folds = Vec::new();
+    if let Some(normalize) = args.normalize {
+        folds.push(format!("{normalize:?}").to_uppercase());
+    }
+    if folding.case {
+        folds.push("case".to_string());
+    }
+    if args.ignore_accents {
+        folds.push("accents".to_string());
+    }
+    let mode = match Mode::new(args) {
+        Mode::Line => "whole lines",
+        Mode::Word => "whole words",
+        Mode::Partial => "anywhere in the line",
+    };
+    let mut lines = vec![
+        format!("query: {:?}", args.query),
+        format!("folding: {}", if folds.is_empty() { "none".to_string() } else { folds.join(", ") }),
+        format!("compiled: {}", compiled(&args.query)),
+        format!("kind: {}", match (args.fuzzy, engine(args)) {
+            (Some(max_edits), _) => format!("fuzzy, up to {max_edits} edits"),
+            (None, Engine::Literal) => "literal text, not a regex".to_string(),
+            (None, Engine::Pcre2) => "regular expression (PCRE2)".to_string(),
+            (None, Engine::Regex) => match Regex::new(&regex_pattern(&args.query, args)) {
+                Ok(_) => "regular expression (regex crate)".to_string(),
+                Err(_) if cfg!(feature = "fancy") => "regular expression (fancy-regex, as the regex crate rejects it)".to_string(),
+                Err(e) => format!("invalid regular expression: {}", e.to_string().lines().last().unwrap_or_default()),
+            },
+            (None, _) => "regular expression (fancy-regex)".to_string(),
+        }),
+        format!("matches: {mode}"),
+    ];
+    if args.engine == Some(Engine::Auto) {
+        lines.push(format!("engine: {:?}, chosen by --engine auto", engine(args)).to_lowercase());
+    }
+    for (name, patterns) in [("any of", &args.any_of), ("all of", &args.all_of), ("none of", &args.none_of)] {
+        if !patterns.is_empty() {
+            lines.push(format!("{name}: {}", patterns.iter().map(|p| compiled(p)).collect::<Vec<_>>().join(", ")));
+        }
+    }
+    if let Some(near) = &args.near {
+        lines.push(format!("near: {} and {} within {} words", prepared(&near.first), prepared(&near.second), near.distance));
+    }
+    if args.invert_match {
+        lines.push("inverted: lines without a match are selected".to_string());
+    }
+    lines.push(format!("strategy: {}", match literal_query(args) {
+        Some(_) => "whole contents scanned for the query at once",
+        None => "each line matched in turn",
+    }));
+    lines
+}
+
+// Builds a matcher for each pattern a line can match on: the query
+// followed by the `--any-of` patterns.
+pub(crate) fn pattern_matchers(args: &CommandArgs) -> Result<Vec<Box<dyn Matcher>>, String> {
+    iter::once(&args.query).chain(&args.any_of).map(|p| pattern_matcher(p, args)).collect()
+}
+
+// Builds the matcher for a single pattern.
+// Returns an error if a regular expression is invalid.
+fn pattern_matcher(pattern: &str, args: &CommandArgs) -> Result<Box<dyn Matcher>, String> {
+    if engine(args) != Engine::Literal {
+        return RegexMatcher::new(pattern, args).map(|m| Box::new(m) as Box<dyn Matcher>);
+    }
+    let query = Prepared::new(pattern, Folding::new(args));
+    let mode = Mode::new(args);
+    Ok(match args.fuzzy {
+        Some(max_edits) => Box::new(FuzzyMatcher {
+            query: String::from_utf8_lossy(query.bytes()).chars().collect(),
+            mode,
+            max_edits,
+        }),
+        // Whole words are found with the same boundaries as a regular
+        // expression, on the already folded line.
+        None if matches!(mode, Mode::Word) => {
+            let pattern = word_pattern(&regex::escape(&String::from_utf8_lossy(query.bytes())), Engine::Regex);
+            Box::new(RegexMatcher::Standard(Regex::new(&pattern).map_err(|e| e.to_string())?))
+        }
+        None => Box::new(ExactMatcher {
+            query: query.bytes().to_vec(),
+            finder: Finder::new(query.bytes()).into_owned(),
+            mode,
+        }),
+    })
+}
+
+// Returns the engine used for `args`. The POSIX dialects are regular
+// expressions, so they use `Regex` unless another engine was chosen.
+// `Auto` is resolved to `Literal` if no pattern has regex syntax.
+pub(crate) fn engine(args: &CommandArgs) -> Engine {
+    match args.engine.unwrap_or_default() {
+        Engine::Literal if args.basic_regexp || args.extended_regexp => Engine::Regex,
+        Engine::Auto => {
+            let mut patterns = iter::once(&args.query).chain(&args.any_of).chain(&args.all_of).chain(&args.none_of);
+            let dialect = args.basic_regexp || args.extended_regexp;
+            match args.fuzzy.is_some() || !dialect && patterns.all(|p| regex::escape(p) == *p) {
+                true => Engine::Literal,
+                false => Engine::Regex,
+            }
+        }
+        engine => engine,
+    }
+}
+
+// Returns the regular expression a pattern is compiled to. Normalization
+// and accents are folded as for lines, but case is left alone so escapes
+// like `\D` keep their meaning, and is ignored with `(?i)` instead.
+fn regex_pattern(pattern: &str, args: &CommandArgs) -> String {
+    let folding = Folding { case: false, ..Folding::new(args) };
+    let pattern = String::from_utf8_lossy(Prepared::new(pattern, folding).bytes()).into_owned();
+    let pattern = match args.basic_regexp || args.extended_regexp {
+        true => posix::translate(&pattern, args.basic_regexp),
+        false => pattern,
+    };
+    let pattern = anchored(pattern, Mode::new(args), engine(args));
+    match args.ignore_case {
+        true => format!("(?i){pattern}"),
+        false => pattern,
+    }
+}
+
+// Wraps a regular expression for `engine` so it only matches the part
+// of a line `mode` asks for.
+fn anchored(pattern: String, mode: Mode, engine: Engine) -> String {
+    match mode {
+        Mode::Line => format!("^(?:{pattern})$"),
+        Mode::Word => word_pattern(&pattern, engine),
+        Mode::Partial => pattern,
+    }
+}
+
+// Wraps a regular expression so it only matches whole words: a match
+// can't have a word character right before or after it. This is the rule
+// for every engine, so `foo` matches in `foo-bar` and `foo bar` matches
+// across the space, but `oo` doesn't match in `foo`. PCRE2 has no
+// half boundaries, so it says the same with lookaround.
+fn word_pattern(pattern: &str, engine: Engine) -> String {
+    match engine {
+        Engine::Pcre2 => format!(r"(?<!\w)(?:{pattern})(?!\w)"),
+        _ => format!(r"\b{{start-half}}(?:{pattern})\b{{end-half}}"),
+    }
+}
+
+// Matches a regular expression, with the regex crate or, for lookaround
+// and backreferences, with fancy-regex or PCRE2.
+enum RegexMatcher {
+    Standard(Regex),
+    #[cfg(feature = "fancy")]
+    Fancy(fancy_regex::Regex),
+    #[cfg(feature = "pcre2")]
+    Pcre2(pcre2::bytes::Regex),
+}
+
+impl RegexMatcher {
+    // Compiles `pattern` with the engine requested by `args`. With the
+    // `fancy` feature, patterns the regex crate rejects are retried with
+    // fancy-regex.
+    fn new(pattern: &str, args: &CommandArgs) -> Result<RegexMatcher, String> {
+        RegexMatcher::compile(&regex_pattern(pattern, args), engine(args))
+    }
+
+    // Compiles an already built regular expression.
+    fn compile(pattern: &str, engine: Engine) -> Result<RegexMatcher, String> {
+        match engine {
+            Engine::Fancy => return RegexMatcher::fancy(pattern),
+            Engine::Pcre2 => return RegexMatcher::pcre2(pattern),
+            _ => {}
+        }
+        match Regex::new(pattern) {
+            Ok(regex) => Ok(RegexMatcher::Standard(regex)),
+            #[cfg(feature = "fancy")]
+            Err(_) => RegexMatcher::fancy(pattern),
+            #[cfg(not(feature = "fancy"))]
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    #[cfg(feature = "fancy")]
+    fn fancy(pattern: &str) -> Result<RegexMatcher, String> {
+        fancy_regex::Regex::new(pattern).map(RegexMatcher::Fancy).map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "fancy"))]
+    fn fancy(_pattern: &str) -> Result<RegexMatcher, String> {
+        Err("--engine fancy needs grepr to be built with the `fancy` feature".to_string())
+    }
+
+    // Compiles a PCRE2 pattern. Unicode is on, so `\w` and `\b` agree
+    // with the other engines about which characters make up words.
+    #[cfg(feature = "pcre2")]
+    fn pcre2(pattern: &str) -> Result<RegexMatcher, String> {
+        pcre2::bytes::RegexBuilder::new()
+            .utf(true)
+            .ucp(true)
+            .jit_if_available(true)
+            .build(pattern)
+            .map(RegexMatcher::Pcre2)
+            .map_err(|e| e.to_string())
+    }
+
+    #[cfg(not(feature = "pcre2"))]
+    fn pcre2(_pattern: &str) -> Result<RegexMatcher, String> {
+        Err("-P and --engine pcre2 need grepr to be built with the `pcre2` feature".to_string())
+    }
+
+    // Returns the name of each capture group, `None` for unnamed ones.
+    // Group 0 is the whole match.
+    fn names(&self) -> Vec<Option<String>> {
+        match self {
+            RegexMatcher::Standard(regex) => regex.capture_names().map(|name| name.map(String::from)).collect(),
+            #[cfg(feature = "fancy")]
+            RegexMatcher::Fancy(regex) => regex.capture_names().map(|name| name.map(String::from)).collect(),
+            #[cfg(feature = "pcre2")]
+            RegexMatcher::Pcre2(regex) => regex.capture_names().to_vec(),
+        }
+    }
+
+    // Returns the byte range of each capture group of every match in
+    // `bytes`, or `None` for a group that took no part in the match.
+    fn captures(&self, bytes: &[u8]) -> Vec<Vec<Option<(usize, usize)>>> {
+        match self {
+            RegexMatcher::Standard(regex) => regex
+                .captures_iter(bytes)
+                .map(|groups| groups.iter().map(|group| group.map(|m| (m.start(), m.end()))).collect())
+                .collect(),
+            #[cfg(feature = "fancy")]
+            RegexMatcher::Fancy(regex) => match std::str::from_utf8(bytes) {
+                Ok(text) => regex
+                    .captures_iter(text)
+                    .map_while(Result::ok)
+                    .map(|groups| groups.iter().map(|group| group.map(|m| (m.start(), m.end()))).collect())
+                    .collect(),
+                Err(_) => Vec::new(),
+            },
+            #[cfg(feature = "pcre2")]
+            RegexMatcher::Pcre2(regex) => regex
+                .captures_iter(bytes)
+                .map_while(Result::ok)
+                .map(|groups| (0..groups.len()).map(|i| groups.get(i).map(|m| (m.start(), m.end()))).collect())
+                .collect(),
+        }
+    }
+}
+
+// The capture groups of one match of the query. Group 0 is the whole
+// match. Each group is a span of the original line, or `None` if the
+// group took no part in the match.
+pub(crate) type Groups = Vec<Option<Span>>;
+
+// Finds the capture groups of the query, for `--group` and `--replace`.
+// A literal query is matched as an escaped regular expression, so it has
+// only group 0.
+pub(crate) struct CaptureMatcher {
+    regex: RegexMatcher,
+    names: Vec<Option<String>>,
+    folding: Folding,
+}
+
+impl CaptureMatcher {
+    // Builds the matcher for the query in `args`.
+    // Returns an error if the query is invalid or fuzzy.
+    pub(crate) fn new(args: &CommandArgs) -> Result<CaptureMatcher, String> {
+        if args.fuzzy.is_some() {
+            return Err("--group and --replace can't be used with --fuzzy".to_string());
+        }
+        let folding = Folding::new(args);
+        let regex = match engine(args) {
+            Engine::Literal => {
+                let query = Prepared::new(&args.query, folding);
+                let pattern = anchored(regex::escape(&String::from_utf8_lossy(query.bytes())), Mode::new(args), Engine::Regex);
+                RegexMatcher::compile(&pattern, Engine::Regex)?
+            }
+            _ => RegexMatcher::new(&args.query, args)?,
+        };
+        Ok(CaptureMatcher { names: regex.names(), regex, folding })
+    }
+
+    // Returns the index of the group named by `group`, either its number
+    // or its name.
+    // Returns an error if the query has no such group.
+    pub(crate) fn index(&self, group: &str) -> Result<usize, String> {
+        let index = match group.parse::<usize>() {
+            Ok(index) => Some(index).filter(|&index| index < self.names.len()),
+            Err(_) => self.names.iter().position(|name| name.as_deref() == Some(group)),
+        };
+        index.ok_or_else(|| format!("the query has no capture group '{group}'"))
+    }
+
+    // Returns the groups of every match of the query in a line.
+    pub(crate) fn captures(&self, line: &str) -> Vec<Groups> {
+        let prepared = Prepared::new(line, self.folding);
+        self.regex
+            .captures(prepared.bytes())
+            .into_iter()
+            .map(|groups| groups.into_iter().map(|group| group.map(|(start, end)| prepared.span(start, end))).collect())
+            .collect()
+    }
+
+    // Expands `template` for one match of `line`, the way the regex crate
+    // does: `$1` and `$name`, or `${1}` and `${name}` when followed by
+    // more text, become the group's text, and `$$` is a `$`. A group
+    // that is unknown or took no part in the match becomes nothing.
+    pub(crate) fn expand(&self, template: &str, groups: &Groups, line: &str) -> String {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(dollar) = rest.find('$') {
+            expanded.push_str(&rest[..dollar]);
+            rest = &rest[dollar + 1..];
+            if let Some(after) = rest.strip_prefix('$') {
+                expanded.push('$');
+                rest = after;
+                continue;
+            }
+            let (name, after) = match rest.strip_prefix('{').and_then(|braced| braced.split_once('}')) {
+                Some((name, after)) => (name, after),
+                None => {
+                    let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+                    rest.split_at(end)
+                }
+            };
+            if name.is_empty() {
+                expanded.push('$');
+                continue;
+            }
+            let span = self.index(name).ok().and_then(|index| groups.get(index).copied().flatten());
+            if let Some(span) = span {
+                expanded.push_str(&line[span.start..span.end]);
+            }
+            rest = after;
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find(&self, line: &Prepared) -> Option<Span> {
+        let (start, end) = match self {
+            RegexMatcher::Standard(regex) => regex.find(line.bytes()).map(|m| (m.start(), m.end()))?,
+            #[cfg(feature = "fancy")]
+            RegexMatcher::Fancy(regex) => {
+                let text = std::str::from_utf8(line.bytes()).ok()?;
+                regex.find(text).ok()?.map(|m| (m.start(), m.end()))?
+            }
+            #[cfg(feature = "pcre2")]
+            RegexMatcher::Pcre2(regex) => regex.find(line.bytes()).ok()?.map(|m| (m.start(), m.end()))?,
+        };
+        Some(line.span(start, end))
+    }
+
+    fn find_all(&self, line: &Prepared) -> Vec<Span> {
+        let ranges: Vec<(usize, usize)> = match self {
+            RegexMatcher::Standard(regex) => regex.find_iter(line.bytes()).map(|m| (m.start(), m.end())).collect(),
+            #[cfg(feature = "fancy")]
+            RegexMatcher::Fancy(regex) => match std::str::from_utf8(line.bytes()) {
+                Ok(text) => regex.find_iter(text).map_while(Result::ok).map(|m| (m.start(), m.end())).collect(),
+                Err(_) => Vec::new(),
+            },
+            #[cfg(feature = "pcre2")]
+            RegexMatcher::Pcre2(regex) => regex.find_iter(line.bytes()).map_while(Result::ok).map(|m| (m.start(), m.end())).collect(),
+        };
+        let spans = ranges.into_iter().filter(|(start, end)| end > start).map(|(start, end)| line.span(start, end)).collect();
+        dedup_spans(spans)
+    }
+}
+
+// Matches lines where at least one of `any`, every one of `all` and none
+// of `none` match. The span is the earliest match from `any`.
+struct BooleanMatcher {
+    any: Vec<Box<dyn Matcher>>,
+    all: Vec<Box<dyn Matcher>>,
+    none: Vec<Box<dyn Matcher>>,
+}
+
+impl Matcher for BooleanMatcher {
+    fn find(&self, line: &Prepared) -> Option<Span> {
+        let span = self.any.iter().filter_map(|m| m.find(line)).min_by_key(|span| span.start)?;
+        let matched = self.all.iter().all(|m| m.find(line).is_some())
+            && !self.none.iter().any(|m| m.find(line).is_some());
+        matched.then_some(span)
+    }
+
+    fn find_all(&self, line: &Prepared) -> Vec<Span> {
+        if self.find(line).is_none() {
+            return Vec::new();
+        }
+        let mut spans: Vec<Span> = self.any.iter().flat_map(|m| m.find_all(line)).collect();
+        spans.sort_by_key(|span| (span.start, span.end));
+        let mut end = 0;
+        spans.retain(|span| {
+            let keep = span.start >= end;
+            end = end.max(span.end);
+            keep
+        });
+        spans
+    }
+}
+
+// Matches any of several literal patterns in one pass over the line,
+// using an Aho-Corasick automaton rather than one search per pattern.
+struct LiteralSetMatcher {
+    automaton: AhoCorasick,
+}
+
+impl LiteralSetMatcher {
+    // Builds the automaton for the query and the `--any-of` patterns.
+    // Returns an error if the patterns are too large for an automaton.
+    fn new(args: &CommandArgs) -> Result<LiteralSetMatcher, String> {
+        let folding = Folding::new(args);
+        let patterns = iter::once(&args.query).chain(&args.any_of).map(|p| Prepared::new(p, folding).bytes().to_vec());
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns)
+            .map_err(|e| e.to_string())?;
+        Ok(LiteralSetMatcher { automaton })
+    }
+}
+
+impl Matcher for LiteralSetMatcher {
+    fn find(&self, line: &Prepared) -> Option<Span> {
+        let m = self.automaton.find(line.bytes())?;
+        Some(line.span(m.start(), m.end()))
+    }
+
+    fn find_all(&self, line: &Prepared) -> Vec<Span> {
+        let spans = self.automaton.find_iter(line.bytes()).map(|m| line.span(m.start(), m.end())).collect();
+        dedup_spans(spans)
+    }
+}
+
+// Matches lines where two terms occur at most `distance` words apart.
+// Each term is looked for inside single words, or must equal a whole word
+// when `whole` is set. Words are lowercased first when `fold_case` is set.
+struct NearMatcher {
+    first: Vec<u8>,
+    second: Vec<u8>,
+    distance: usize,
+    whole: bool,
+    fold_case: bool,
+}
+
+impl Matcher for NearMatcher {
+    fn find(&self, line: &Prepared) -> Option<Span> {
+        let bytes = line.bytes();
+        let words: Vec<(usize, usize)> = words(bytes).collect();
+        let contains = |index: usize, term: &[u8]| {
+            let word = fold_word(&bytes[words[index].0..words[index].1], self.fold_case);
+            let word = &word[..];
+            if self.whole {
+                word == term
+            } else {
+                term.is_empty() || word.windows(term.len()).any(|window| window == term)
+            }
+        };
+        let seconds: Vec<usize> = (0..words.len()).filter(|&i| contains(i, &self.second)).collect();
+        (0..words.len())
+            .filter(|&i| contains(i, &self.first))
+            .find_map(|i| {
+                let j = *seconds.iter().find(|&&j| j != i && j.abs_diff(i) <= self.distance)?;
+                Some(line.span(words[i.min(j)].0, words[i.max(j)].1))
+            })
+    }
+}
+
+// Returns a word lowercased if `fold_case` is set.
+fn fold_word(word: &[u8], fold_case: bool) -> Cow<'_, [u8]> {
+    match fold_case {
+        true => Cow::Owned(String::from_utf8_lossy(word).to_lowercase().into_bytes()),
+        false => Cow::Borrowed(word),
+    }
+}
+
+// Which part of a line has to match the query.
+enum Mode {
+    Line,
+    Word,
+    Partial,
+}
+
+impl Mode {
+    fn new(args: &CommandArgs) -> Mode {
+        if args.line {
+            Mode::Line
+        } else if args.word {
+            Mode::Word
+        } else {
+            Mode::Partial
+        }
+    }
+}
+
+// Returns the regex matching the separators between words.
+// It is compiled once and shared by every matcher.
+fn word_separator() -> &'static Regex {
+    static WORD_SEPARATOR: OnceLock<Regex> = OnceLock::new();
+    WORD_SEPARATOR.get_or_init(|| Regex::new(r"\W+").unwrap())
+}
+
+// Splits prepared bytes into words, returning the byte range of each.
+fn words(bytes: &[u8]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut start = 0;
+    let separators = word_separator().find_iter(bytes).map(|m| (m.start(), m.end()));
+    separators.chain(iter::once((bytes.len(), bytes.len()))).map(move |(end, next)| {
+        let word = (start, end);
+        start = next;
+        word
+    })
+}
+
+// Matches the query exactly, anywhere in the line or as the whole line.
+// Whole words are matched by `word_pattern` instead.
+struct ExactMatcher {
+    query: Vec<u8>,
+    finder: Finder<'static>,
+    mode: Mode,
+}
+
+impl Matcher for ExactMatcher {
+    fn find(&self, line: &Prepared) -> Option<Span> {
+        let bytes = line.bytes();
+        let query = &self.query[..];
+        match &self.mode {
+            Mode::Line => (bytes == query).then(|| line.span(0, bytes.len())),
+            Mode::Word | Mode::Partial => self.finder.find(bytes).map(|start| line.span(start, start + query.len())),
+        }
+    }
+
+    fn find_all(&self, line: &Prepared) -> Vec<Span> {
+        let bytes = line.bytes();
+        let query = &self.query[..];
+        match &self.mode {
+            Mode::Word | Mode::Partial if !query.is_empty() => {
+                let spans = self.finder.find_iter(bytes).map(|start| line.span(start, start + query.len())).collect();
+                dedup_spans(spans)
+            }
+            _ => self.find(line).into_iter().collect(),
+        }
+    }
+}
+
+// Matches text within `max_edits` insertions, deletions or substitutions
+// of the query, counted in characters.
+struct FuzzyMatcher {
+    query: Vec<char>,
+    mode: Mode,
+    max_edits: usize,
+}
+
+impl FuzzyMatcher {
+    // Returns true if all of `text` is within `max_edits` of the query.
+    fn within(&self, text: &[u8]) -> bool {
+        let text: Vec<char> = String::from_utf8_lossy(text).chars().collect();
+        edit_distance(&self.query, &text) <= self.max_edits
+    }
+}
+
+impl Matcher for FuzzyMatcher {
+    fn find(&self, line: &Prepared) -> Option<Span> {
+        let bytes = line.bytes();
+        match &self.mode {
+            Mode::Line => self.within(bytes).then(|| line.span(0, bytes.len())),
+            Mode::Word => words(bytes)
+                .find(|&(start, end)| self.within(&bytes[start..end]))
+                .map(|(start, end)| line.span(start, end)),
+            Mode::Partial => {
+                let text = String::from_utf8_lossy(bytes);
+                let chars: Vec<(usize, char)> = text.char_indices().collect();
+                let (start, end) = fuzzy_find(&self.query, &chars, self.max_edits)?;
+                let offset = |index: usize| chars.get(index).map_or(bytes.len(), |c| c.0);
+                Some(line.span(offset(start), offset(end)))
+            }
+        }
+    }
+
+    fn find_all(&self, line: &Prepared) -> Vec<Span> {
+        let bytes = line.bytes();
+        match &self.mode {
+            Mode::Line => self.find(line).into_iter().collect(),
+            Mode::Word => words(bytes)
+                .filter(|&(start, end)| self.within(&bytes[start..end]))
+                .map(|(start, end)| line.span(start, end))
+                .collect(),
+            Mode::Partial => {
+                let text = String::from_utf8_lossy(bytes);
+                let chars: Vec<(usize, char)> = text.char_indices().collect();
+                let offset = |index: usize| chars.get(index).map_or(bytes.len(), |c| c.0);
+                let mut spans = Vec::new();
+                let mut from = 0;
+                while let Some((start, end)) = fuzzy_find(&self.query, &chars[from..], self.max_edits) {
+                    spans.push(line.span(offset(from + start), offset(from + end)));
+                    // A query within `max_edits` of nothing matches everywhere.
+                    if end == 0 || from + end == chars.len() {
+                        break;
+                    }
+                    from += end;
+                }
+                dedup_spans(spans)
+            }
+        }
+    }
+}
+
+// Drops repeated spans, which happen when several prepared matches come
+// from the same original characters.
+fn dedup_spans(mut spans: Vec<Span>) -> Vec<Span> {
+    spans.dedup();
+    spans
+}
+
+// Returns the Levenshtein distance between two strings of characters.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=a.len()).collect();
+    for (j, cb) in b.iter().enumerate() {
+        let mut current = vec![j + 1; a.len() + 1];
+        for (i, ca) in a.iter().enumerate() {
+            let substitute = previous[i] + usize::from(ca != cb);
+            current[i + 1] = substitute.min(previous[i + 1] + 1).min(current[i] + 1);
+        }
+        previous = current;
+    }
+    previous[a.len()]
+}
+
+// Finds the first substring of `text` within `max_edits` of `pattern`.
+// This is Sellers' algorithm: a match may start at any character, so the
+// first row of the edit distance table is all zeros. Each cell also keeps
+// the character index where its alignment started. Once a match is found
+// it is extended while doing so lowers the distance.
+// Returns the character range of the match within `text`.
+fn fuzzy_find(pattern: &[char], text: &[(usize, char)], max_edits: usize) -> Option<(usize, usize)> {
+    let mut previous: Vec<(usize, usize)> = (0..=pattern.len()).map(|i| (i, 0)).collect();
+    let mut best: Option<(usize, usize, usize)> = None;
+    if pattern.len() <= max_edits {
+        best = Some((pattern.len(), 0, 0));
+    }
+    for (j, &(_, c)) in text.iter().enumerate() {
+        let mut current = vec![(0, j + 1); pattern.len() + 1];
+        for (i, &p) in pattern.iter().enumerate() {
+            let substitute = (previous[i].0 + usize::from(p != c), previous[i].1);
+            let skip_text = (previous[i + 1].0 + 1, previous[i + 1].1);
+            let skip_pattern = (current[i].0 + 1, current[i].1);
+            current[i + 1] = [substitute, skip_text, skip_pattern].into_iter().min_by_key(|cell| cell.0).unwrap();
+        }
+        let (cost, start) = current[pattern.len()];
+        match best {
+            Some((best_cost, _, _)) if cost < best_cost => best = Some((cost, start, j + 1)),
+            Some(_) => break,
+            None if cost <= max_edits => best = Some((cost, start, j + 1)),
+            None => {}
+        }
+        previous = current;
+    }
+    best.map(|(_, start, end)| (start, end))
+}
+
+// Removes combining marks from decomposed text and transliterates the
+// letters that carry an accent without decomposing, such as "ø".
+fn strip_accents(chars: impl Iterator<Item = char>) -> impl Iterator<Item = char> {
+    chars.filter(|c| !is_combining_mark(*c)).flat_map(|c| {
+        let folded = match c {
+            'ø' => "o",
+            'Ø' => "O",
+            'ł' => "l",
+            'Ł' => "L",
+            'đ' => "d",
+            'Đ' => "D",
+            'ħ' => "h",
+            'Ħ' => "H",
+            'ı' => "i",
+            'ß' => "ss",
+            'æ' => "ae",
+            'Æ' => "AE",
+            'œ' => "oe",
+            'Œ' => "OE",
+            'þ' => "th",
+            'Þ' => "TH",
+            _ => return Transliterated::Char(Some(c)),
+        };
+        Transliterated::Str(folded.chars())
+    })
+}
+
+// The output of `strip_accents` for a single character.
+enum Transliterated {
+    Char(Option<char>),
+    Str(std::str::Chars<'static>),
+}
+
+impl Iterator for Transliterated {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        match self {
+            Transliterated::Char(c) => c.take(),
+            Transliterated::Str(chars) => chars.next(),
+        }
+    }
+}
+
+// Splits a line into the byte ranges that are normalized independently.
+// Each range starts at a character with a combining class of zero, so
+// combining marks stay with the character they modify. Hangul vowel and
+// final jamo also stay with the preceding syllable so they can compose.
+fn segments(line: &str) -> impl Iterator<Item = (usize, usize)> + '_ {
+    let mut starts = line
+        .char_indices()
+        .filter(|(index, c)| *index == 0 || (canonical_combining_class(*c) == 0 && !('\u{1160}'..='\u{11FF}').contains(c)))
+        .map(|(index, _)| index)
+        .peekable();
+    iter::from_fn(move || {
+        let start = starts.next()?;
+        Some((start, starts.peek().copied().unwrap_or(line.len())))
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_span_after_case_expansion() {
+        // 'İ' lowercases to two characters, so the folded line is longer.
+        let args = CommandArgs::new("stanbul".to_string(), PathBuf::new(), true, false, false, false);
+        let line = Prepared::new("İstanbul", Folding::new(&args));
+        let span = new_matcher(&args).unwrap().find(&line).unwrap();
+
+        assert_eq!(&"İstanbul"[span.start..span.end], "stanbul")
+    }
+
+    #[test]
+    fn test_span_inside_expanded_char() {
+        let args = CommandArgs::new("i".to_string(), PathBuf::new(), true, false, false, false);
+        let line = Prepared::new("aİb", Folding::new(&args));
+        let span = new_matcher(&args).unwrap().find(&line).unwrap();
+
+        assert_eq!(&"aİb"[span.start..span.end], "İ")
+    }
+
+    #[test]
+    fn test_span_nfc_decomposed_line() {
+        let args = CommandArgs::new("caf\u{e9}".to_string(), PathBuf::new(), false, false, false, false)
+            .with_normalize(Some(Normalization::Nfc));
+        let contents = "un cafe\u{301} noir";
+        let line = Prepared::new(contents, Folding::new(&args));
+        let span = new_matcher(&args).unwrap().find(&line).unwrap();
+
+        assert_eq!(&contents[span.start..span.end], "cafe\u{301}")
+    }
+
+    #[test]
+    fn test_span_ignore_accents() {
+        let args = CommandArgs::new("resume".to_string(), PathBuf::new(), true, false, true, false)
+            .with_ignore_accents(true);
+        let contents = "Mon Résumé, Søren";
+        let line = Prepared::new(contents, Folding::new(&args));
+        let span = new_matcher(&args).unwrap().find(&line).unwrap();
+
+        assert_eq!(&contents[span.start..span.end], "Résumé");
+        assert_eq!(Prepared::new(contents, Folding::new(&args)).bytes(), b"mon resume, soren")
+    }
+
+    #[test]
+    fn test_fuzzy_partial_typo() {
+        let args = CommandArgs::new("recieve".to_string(), PathBuf::new(), false, false, false, false)
+            .with_fuzzy(Some(2));
+        let contents = "we did not receive it";
+        let line = Prepared::new(contents, Folding::new(&args));
+        let span = new_matcher(&args).unwrap().find(&line).unwrap();
+
+        assert_eq!(&contents[span.start..span.end], "receive")
+    }
+
+    #[test]
+    fn test_fuzzy_word_too_far() {
+        let args = CommandArgs::new("recieve".to_string(), PathBuf::new(), false, false, true, false)
+            .with_fuzzy(Some(1));
+        let line = Prepared::new("we did not receive it", Folding::new(&args));
+
+        assert_eq!(new_matcher(&args).unwrap().find(&line), None)
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let kitten: Vec<char> = "kitten".chars().collect();
+        let sitting: Vec<char> = "sitting".chars().collect();
+
+        assert_eq!(edit_distance(&kitten, &sitting), 3)
+    }
+
+    #[test]
+    fn test_segments_keep_combining_marks() {
+        let segments: Vec<_> = segments("e\u{301}a").collect();
+
+        assert_eq!(segments, vec![(0, 3), (3, 4)])
+    }
+
+    #[test]
+    fn test_literal_set_leftmost_longest() {
+        let args = CommandArgs::new("beta".to_string(), PathBuf::new(), true, false, false, false)
+            .with_any_of(vec!["alpha".to_string(), "alphabet".to_string()]);
+        let contents = "the ALPHABET before beta";
+        let line = Prepared::new(contents, Folding::new(&args));
+        let spans = new_matcher(&args).unwrap().find_all(&line);
+
+        assert_eq!(spans.iter().map(|s| &contents[s.start..s.end]).collect::<Vec<_>>(), vec!["ALPHABET", "beta"])
+    }
+
+    #[test]
+    fn test_line_buffer_reuse() {
+        let args = CommandArgs::new("é".to_string(), PathBuf::new(), true, false, false, false);
+        let matcher = new_matcher(&args).unwrap();
+        let mut buffer = LineBuffer::default();
+        let first = matcher.find(&buffer.prepare("ÉÉÉÉ long first line", Folding::new(&args)));
+        let second = matcher.find(&buffer.prepare("aÉ", Folding::new(&args)));
+
+        assert_eq!(first, Some(Span { start: 0, end: 2 }));
+        assert_eq!(second, Some(Span { start: 1, end: 3 }))
+    }
+
+    #[test]
+    fn test_case_only_ascii_and_unicode_lines() {
+        let args = CommandArgs::new("kelvin".to_string(), PathBuf::new(), true, false, false, false);
+        let matcher = new_matcher(&args).unwrap();
+        let mut buffer = LineBuffer::default();
+        let ascii = matcher.find(&buffer.prepare("in KELVIN", Folding::new(&args)));
+        let unicode = matcher.find(&buffer.prepare("in \u{212a}elvin", Folding::new(&args)));
+
+        assert_eq!(ascii, Some(Span { start: 3, end: 9 }));
+        assert_eq!(unicode, Some(Span { start: 3, end: 11 }))
+    }
+
+    #[test]
+    fn test_explain_folding() {
+        let args = CommandArgs::new("Café".to_string(), PathBuf::new(), true, false, true, false);
+        let lines = explain(&args);
+
+        assert!(lines.contains(&"folding: case".to_string()));
+        assert!(lines.contains(&"compiled: \"café\"".to_string()));
+        assert!(lines.contains(&"matches: whole words".to_string()));
+        assert!(lines.contains(&"strategy: each line matched in turn".to_string()))
+    }
+
+    #[test]
+    fn test_regex_matcher_folding() {
+        let args = CommandArgs::new(r"\D+\d".to_string(), PathBuf::new(), true, false, true, false)
+            .with_engine(Engine::Regex);
+        let line = Prepared::new("id AB1 x", Folding::new(&args));
+
+        assert_eq!(regex_pattern(&args.query, &args), r"(?i)\b{start-half}(?:\D+\d)\b{end-half}");
+        assert_eq!(new_matcher(&args).unwrap().find(&line), Some(Span { start: 0, end: 6 }))
+    }
+
+    #[test]
+    fn test_regex_matcher_invalid() {
+        let args = CommandArgs::new("(unclosed".to_string(), PathBuf::new(), false, false, false, false)
+            .with_engine(Engine::Regex);
+
+        assert!(new_matcher(&args).is_err())
+    }
+
+    #[cfg(feature = "fancy")]
+    #[test]
+    fn test_regex_matcher_fancy_fallback() {
+        let args = CommandArgs::new(r"(\w+) \1".to_string(), PathBuf::new(), false, false, false, false)
+            .with_engine(Engine::Regex);
+        let line = Prepared::new("it is is done", Folding::new(&args));
+
+        assert_eq!(new_matcher(&args).unwrap().find(&line), Some(Span { start: 3, end: 8 }))
+    }
+
+    #[cfg(feature = "pcre2")]
+    #[test]
+    fn test_regex_matcher_pcre2() {
+        let args = CommandArgs::new(r"(?<=\$)\d+".to_string(), PathBuf::new(), false, false, true, false)
+            .with_engine(Engine::Pcre2);
+        let line = Prepared::new("costs 5x or $12", Folding::new(&args));
+
+        assert_eq!(new_matcher(&args).unwrap().find(&line), Some(Span { start: 13, end: 15 }))
+    }
+
+    #[cfg(not(feature = "pcre2"))]
+    #[test]
+    fn test_regex_matcher_pcre2_needs_feature() {
+        let args = CommandArgs::new("a".to_string(), PathBuf::new(), false, false, false, false)
+            .with_engine(Engine::Pcre2);
+
+        assert!(new_matcher(&args).err().unwrap().contains("`pcre2` feature"))
+    }
+
+    #[test]
+    fn test_engine_auto() {
+        let args = CommandArgs::new("v1".to_string(), PathBuf::new(), false, false, false, false)
+            .with_engine(Engine::Auto);
+        assert_eq!(engine(&args), Engine::Literal);
+
+        let args = args.with_any_of(vec![r"v\d".to_string()]);
+        assert_eq!(engine(&args), Engine::Regex)
+    }
+
+    #[test]
+    fn test_word_boundaries_same_for_every_engine() {
+        for engine in [Engine::Literal, Engine::Regex] {
+            let find = |query: &str, line: &str| {
+                let args = CommandArgs::new(query.to_string(), PathBuf::new(), true, false, true, false).with_engine(engine);
+                new_matcher(&args).unwrap().find(&Prepared::new(line, Folding::new(&args)))
+            };
+
+            assert_eq!(find("foo", "a FOO-bar"), Some(Span { start: 2, end: 5 }));
+            assert_eq!(find("foo bar", "a foo bar!"), Some(Span { start: 2, end: 9 }));
+            assert_eq!(find("oo", "foo"), None)
+        }
+    }
+
+    #[test]
+    fn test_capture_matcher_groups_and_expand() {
+        let args = CommandArgs::new(r"(?<user>\w+)@(\w+)".to_string(), PathBuf::new(), false, false, false, false)
+            .with_engine(Engine::Regex);
+        let captures = CaptureMatcher::new(&args).unwrap();
+        let line = "to: ann@example, bob@test";
+        let matches = captures.captures(line);
+
+        assert_eq!(matches.len(), 2);
+        assert_eq!(captures.index("user"), Ok(1));
+        assert!(captures.index("3").is_err());
+        assert_eq!(captures.expand("$2:${user}s $$1 $9$", &matches[1], line), "test:bobs $1 $")
+    }
+
+    #[test]
+    fn test_capture_matcher_literal_folded() {
+        let args = CommandArgs::new("Résumé".to_string(), PathBuf::new(), true, false, false, false)
+            .with_ignore_accents(true);
+        let captures = CaptureMatcher::new(&args).unwrap();
+        let line = "my RESUME.txt";
+
+        assert_eq!(captures.captures(line), vec![vec![Some(Span { start: 3, end: 9 })]]);
+        assert!(captures.index("1").is_err())
+    }
+}
@@ -0,0 +1,140 @@
+//! Translations of the messages grepr prints while running.
+//!
+//! Covers the `--stats` summary, the notes about skipped and unreadable
+//! files and the binary's `Application error` prefix. Read errors other
+//! than a missing file or a denied permission keep the system's wording.
+//! The language comes from `--lang`, or else from `LC_ALL`, `LC_MESSAGES`
+//! or `LANG`, and falls back to English. Help text is not translated.
+use std::env;
+use std::io;
+use crate::Lang;
+
+
+// A message that has a translation for each `Lang`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Message {
+    MatchedLines,
+    LinesSearched,
+    BytesSearched,
+    LongLinesSkipped,
+    LongLinesTruncated,
+    TimedOut,
+    SkippedSpecialFile,
+    NotFound,
+    PermissionDenied,
+    ApplicationError,
+}
+
+impl Lang {
+    /// Picks the language from the locale environment variables.
+    ///
+    /// The first of `LC_ALL`, `LC_MESSAGES` and `LANG` that is set and
+    /// not empty decides, so `de_DE.UTF-8` gives `De`. Anything without a
+    /// translation gives `En`.
+    ///
+    pub fn from_env() -> Lang {
+        let locale = ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(env::var_os)
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        Lang::from_locale(&locale.to_string_lossy())
+    }
+
+    // Picks the language from a locale name such as `fr_CA.UTF-8`.
+    fn from_locale(locale: &str) -> Lang {
+        match locale.split(['_', '.', '-', '@']).next().unwrap_or("") {
+            "de" => Lang::De,
+            "es" => Lang::Es,
+            "fr" => Lang::Fr,
+            _ => Lang::En,
+        }
+    }
+
+    /// Returns the prefix the binary prints before a fatal error, such as
+    /// `Application error` in English.
+    ///
+    pub fn application_error(self) -> &'static str {
+        self.text(Message::ApplicationError)
+    }
+
+    // Rewords a missing file or a denied permission in this language,
+    // keeping the error kind. Other errors are returned unchanged.
+    pub(crate) fn io_error(self, e: io::Error) -> io::Error {
+        match e.kind() {
+            io::ErrorKind::NotFound => io::Error::new(e.kind(), self.text(Message::NotFound)),
+            io::ErrorKind::PermissionDenied => io::Error::new(e.kind(), self.text(Message::PermissionDenied)),
+            _ => e,
+        }
+    }
+
+    // Returns the text of `message` in this language.
+    pub(crate) fn text(self, message: Message) -> &'static str {
+        use Message::*;
+        match (self, message) {
+            (Lang::En, MatchedLines) => "matched lines",
+            (Lang::En, LinesSearched) => "lines searched",
+            (Lang::En, BytesSearched) => "bytes searched",
+            (Lang::En, LongLinesSkipped) => "long lines skipped",
+            (Lang::En, LongLinesTruncated) => "long lines truncated",
+            (Lang::En, TimedOut) => "timed out, skipped",
+            (Lang::En, SkippedSpecialFile) => "skipping special file (use --devices read to search it)",
+            (Lang::En, NotFound) => "No such file or directory",
+            (Lang::En, PermissionDenied) => "Permission denied",
+            (Lang::En, ApplicationError) => "Application error",
+            (Lang::De, MatchedLines) => "übereinstimmende Zeilen",
+            (Lang::De, LinesSearched) => "durchsuchte Zeilen",
+            (Lang::De, BytesSearched) => "durchsuchte Bytes",
+            (Lang::De, LongLinesSkipped) => "lange Zeilen übersprungen",
+            (Lang::De, LongLinesTruncated) => "lange Zeilen gekürzt",
+            (Lang::De, TimedOut) => "Zeitlimit überschritten, übersprungen",
+            (Lang::De, SkippedSpecialFile) => "Spezialdatei übersprungen (mit --devices read wird sie durchsucht)",
+            (Lang::De, NotFound) => "Datei oder Verzeichnis nicht gefunden",
+            (Lang::De, PermissionDenied) => "Keine Berechtigung",
+            (Lang::De, ApplicationError) => "Anwendungsfehler",
+            (Lang::Es, MatchedLines) => "líneas coincidentes",
+            (Lang::Es, LinesSearched) => "líneas buscadas",
+            (Lang::Es, BytesSearched) => "bytes buscados",
+            (Lang::Es, LongLinesSkipped) => "líneas largas omitidas",
+            (Lang::Es, LongLinesTruncated) => "líneas largas truncadas",
+            (Lang::Es, TimedOut) => "tiempo agotado, omitido",
+            (Lang::Es, SkippedSpecialFile) => "archivo especial omitido (use --devices read para buscar en él)",
+            (Lang::Es, NotFound) => "No existe el archivo o el directorio",
+            (Lang::Es, PermissionDenied) => "Permiso denegado",
+            (Lang::Es, ApplicationError) => "Error de la aplicación",
+            (Lang::Fr, MatchedLines) => "lignes correspondantes",
+            (Lang::Fr, LinesSearched) => "lignes parcourues",
+            (Lang::Fr, BytesSearched) => "octets parcourus",
+            (Lang::Fr, LongLinesSkipped) => "lignes longues ignorées",
+            (Lang::Fr, LongLinesTruncated) => "lignes longues tronquées",
+            (Lang::Fr, TimedOut) => "délai dépassé, ignoré",
+            (Lang::Fr, SkippedSpecialFile) => "fichier spécial ignoré (utilisez --devices read pour le parcourir)",
+            (Lang::Fr, NotFound) => "Aucun fichier ou dossier de ce nom",
+            (Lang::Fr, PermissionDenied) => "Permission refusée",
+            (Lang::Fr, ApplicationError) => "Erreur de l'application",
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_locale() {
+        assert_eq!(Lang::from_locale("de_DE.UTF-8"), Lang::De);
+        assert_eq!(Lang::from_locale("fr"), Lang::Fr);
+        assert_eq!(Lang::from_locale("C.UTF-8"), Lang::En);
+        assert_eq!(Lang::from_locale(""), Lang::En)
+    }
+
+    #[test]
+    fn test_io_error() {
+        let e = Lang::De.io_error(io::Error::from(io::ErrorKind::NotFound));
+        assert_eq!(e.kind(), io::ErrorKind::NotFound);
+        assert_eq!(e.to_string(), "Datei oder Verzeichnis nicht gefunden");
+        let e = Lang::De.io_error(io::Error::other("disk on fire"));
+        assert_eq!(e.to_string(), "disk on fire")
+    }
+}
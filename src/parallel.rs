@@ -0,0 +1,233 @@
+//! Searching many files at once on a pool of threads.
+//!
+//! Each file is read and searched on one of the worker threads, and its
+//! results are buffered until every earlier file has been passed to the
+//! sink. The sink runs on the calling thread, so it needs no
+//! synchronization and always sees files in the order they were given.
+//! That order also decides which results count towards `max_total`, so
+//! the same ones are kept as when the files are searched one by one.
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use crate::{binary_patterns, CommandArgs, OwnedMatch, RunArgs, Search, SearchStats, Stats};
+
+
+/// The files that could not be searched by `search_paths_parallel`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathErrors {
+    /// Each path that failed, with the reason.
+    pub errors: Vec<(PathBuf, String)>,
+    /// Counts from the files that were searched.
+    pub stats: SearchStats,
+}
+
+// The results of searching one file, or why it couldn't be searched.
+type FileResult = Result<(Vec<OwnedMatch>, Stats), String>;
+
+/// Searches several files in parallel with the options in `args`.
+///
+/// The path in `args` is ignored. Each result is passed to `sink` along
+/// with the path it was found in. Files are handed to `sink` in the
+/// order of `paths`, with all the results of a file together, no matter
+/// which finishes first. With `max_total`, only the first results in
+/// that order are passed on, and no more files are started once that
+/// many have been.
+///
+/// # Returns
+/// Returns the `SearchStats` of every file, or `PathErrors` listing the
+/// files that could not be read or searched. Results from the other
+/// files are still passed to `sink`. If `binary` is set and a pattern
+/// has a bad `\xNN` escape, every file is listed.
+///
+/// # Example
+/// ```no_run
+/// # use crate::grepr::{search_paths_parallel, CommandArgs};
+/// # use std::path::PathBuf;
+/// let args = CommandArgs::new("TODO".to_string(), PathBuf::new(), false, false, false, false);
+/// let paths = vec![PathBuf::from("src/lib.rs"), PathBuf::from("src/main.rs")];
+///
+/// let stats = search_paths_parallel(&paths, &args, |path, m| {
+///     println!("{}:{}: {}", path.display(), m.number, m.line);
+/// });
+/// ```
+///
+pub fn search_paths_parallel<F>(paths: &[PathBuf], args: &CommandArgs, mut sink: F) -> Result<SearchStats, PathErrors>
+where
+    F: FnMut(&Path, OwnedMatch),
+{
+    let start = Instant::now();
+    let decoded;
+    let args = match args.binary {
+        true => match binary_patterns(args) {
+            Ok(args) => {
+                decoded = args;
+                &decoded
+            }
+            Err(e) => {
+                let errors = paths.iter().map(|path| (path.clone(), e.clone())).collect();
+                let stats = SearchStats { files_skipped: paths.len(), ..SearchStats::default() };
+                return Err(PathErrors { errors, stats });
+            }
+        },
+        false => args,
+    };
+    let full = |count: usize| args.max_total.is_some_and(|max| count >= max);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(paths.len());
+    let next = AtomicUsize::new(0);
+    // The number of results passed to `sink` so far.
+    let sent = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel::<(usize, FileResult)>();
+    let mut stats = SearchStats::default();
+    let mut errors = Vec::new();
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let sender = sender.clone();
+            let next = &next;
+            let sent = &sent;
+            scope.spawn(move || loop {
+                if full(sent.load(Ordering::Relaxed)) {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(index) else {
+                    break;
+                };
+                if sender.send((index, search_one(path, args))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        // Results that arrived before an earlier file finished.
+        let mut pending = BTreeMap::new();
+        let mut emitted = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&emitted) {
+                let path = &paths[emitted];
+                emitted += 1;
+                let count = sent.load(Ordering::Relaxed);
+                // Files after the last result needed are left out, as
+                // they wouldn't have been searched one by one.
+                if full(count) {
+                    continue;
+                }
+                match result {
+                    Ok((matches, file_stats)) => {
+                        let room = args.max_total.map_or(usize::MAX, |max| max - count);
+                        sent.store(count + matches.len().min(room), Ordering::Relaxed);
+                        matches.into_iter().take(room).for_each(|m| sink(path, m));
+                        add_stats(&mut stats.lines, file_stats);
+                        stats.files_searched += 1;
+                    }
+                    Err(e) => {
+                        errors.push((path.clone(), e));
+                        stats.files_skipped += 1;
+                    }
+                }
+            }
+        }
+    });
+    stats.elapsed = start.elapsed();
+    match errors.is_empty() {
+        true => Ok(stats),
+        false => Err(PathErrors { errors, stats }),
+    }
+}
+
+// Reads and searches a single file, stopping after `max_total` results.
+fn search_one(path: &Path, args: &CommandArgs) -> FileResult {
+    let args = CommandArgs { path: path.to_path_buf(), ..args.clone() };
+    let contents = args.read().map_err(|e| e.to_string())?;
+    let search = Search::new(&contents);
+    let mut matches = Vec::new();
+    let _ = search.find_with(&args, |m| {
+        matches.push(OwnedMatch::from(m));
+        match args.max_total.is_some_and(|max| matches.len() >= max) {
+            true => ControlFlow::Break(()),
+            false => ControlFlow::Continue(()),
+        }
+    }).map_err(|e| e.to_string())?;
+    Ok((matches, search.get_stats()))
+}
+
+// Adds the counts from one file to the running totals.
+fn add_stats(total: &mut Stats, stats: Stats) {
+    total.lines_searched += stats.lines_searched;
+    total.lines_matched += stats.lines_matched;
+    total.lines_skipped += stats.lines_skipped;
+    total.lines_truncated += stats.lines_truncated;
+    total.bytes_searched += stats.bytes_searched;
+}
+
+impl fmt::Display for PathErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of the files could not be searched", self.errors.len())
+    }
+}
+
+impl Error for PathErrors {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_search_paths_parallel_order_and_errors() {
+        let dir = std::env::temp_dir().join(format!("grepr-parallel-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<PathBuf> = (0..6).map(|i| dir.join(format!("{i}.txt"))).collect();
+        for (i, path) in paths.iter().enumerate().skip(1) {
+            fs::write(path, "test\nnothing\n".repeat(i)).unwrap();
+        }
+        let args = CommandArgs::new("test".to_string(), PathBuf::new(), false, false, false, false);
+        let mut seen = Vec::new();
+        let result = search_paths_parallel(&paths, &args, |path, m| seen.push((path.to_path_buf(), m.number)));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let errors = result.unwrap_err();
+        assert_eq!(errors.errors.len(), 1);
+        assert_eq!(errors.errors[0].0, paths[0]);
+        assert_eq!(errors.stats.lines.lines_matched, 1 + 2 + 3 + 4 + 5);
+        assert_eq!((errors.stats.files_searched, errors.stats.files_skipped), (5, 1));
+        assert_eq!(seen[..3], [(paths[1].clone(), 0), (paths[2].clone(), 0), (paths[2].clone(), 2)]);
+        assert!(seen.windows(2).all(|pair| pair[0].0 <= pair[1].0))
+    }
+
+    #[test]
+    fn test_search_paths_parallel_binary_max_total() {
+        let dir = std::env::temp_dir().join(format!("grepr-parallel-total-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let paths: Vec<PathBuf> = (0..4).map(|i| dir.join(format!("{i}.bin"))).collect();
+        for (path, contents) in paths.iter().zip([&b"\xde\xad\n\x00\xde\xad\n"[..], b"\xde\n", b"x\n\xde\xad\xbe\n\xde\xad\n", b"\xde\xad\n"]) {
+            fs::write(path, contents).unwrap();
+        }
+        let args = CommandArgs::new(r"\xde\xad".to_string(), PathBuf::new(), false, false, false, false)
+            .with_binary(true)
+            .with_max_total(Some(3));
+        let mut seen = Vec::new();
+        let result = search_paths_parallel(&paths, &args, |path, m| seen.push((path.to_path_buf(), m.number)));
+        let mut expected = Vec::new();
+        for path in &paths {
+            let remaining = 3 - expected.len();
+            let file_args = CommandArgs { path: path.clone(), ..binary_patterns(&args).unwrap() }.with_max_total(Some(remaining));
+            let contents = file_args.read().unwrap();
+            let search = crate::search_str(&contents, &file_args).unwrap();
+            expected.extend(search.into_iter().map(|(number, _)| (path.clone(), number)));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(result.unwrap().files_searched, 3);
+        assert_eq!(seen, expected);
+        assert_eq!(seen, vec![(paths[0].clone(), 0), (paths[0].clone(), 1), (paths[2].clone(), 1)])
+    }
+}
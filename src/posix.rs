@@ -0,0 +1,146 @@
+//! Translating POSIX regular expressions for the regex crate.
+//!
+//! Used for `-G` and `-E`, so patterns written for system grep keep
+//! working. Basic expressions swap the meaning of the escaped and bare
+//! forms of `( ) { } | + ?`, and only treat `^` and `$` as anchors at
+//! the ends of the pattern or of a group. In both dialects a backslash
+//! inside a bracket expression is a plain backslash, and a `*` with
+//! nothing to repeat is a plain star. GNU escapes such as `\w` and `\<`
+//! are kept, and backreferences are left for fancy-regex.
+
+
+// Translates a POSIX pattern, basic if `basic` is set and extended
+// otherwise, into the syntax of the regex crate.
+pub(crate) fn translate(pattern: &str, basic: bool) -> String {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut translated = String::with_capacity(pattern.len());
+    // True where a `*` would have nothing to repeat.
+    let mut at_start = true;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let was_start = at_start;
+        at_start = false;
+        match c {
+            '[' => {
+                i = bracket(&chars, i, &mut translated);
+                continue;
+            }
+            '\\' if basic && matches!(next, Some('(' | '|')) => {
+                translated.push(next.unwrap());
+                at_start = true;
+                i += 1;
+            }
+            '\\' if basic && matches!(next, Some(')' | '{' | '}' | '+' | '?')) => {
+                translated.push(next.unwrap());
+                i += 1;
+            }
+            '\\' => {
+                translated.push('\\');
+                translated.extend(next);
+                i += 1;
+            }
+            '(' | ')' | '{' | '}' | '|' | '+' | '?' if basic => {
+                translated.push('\\');
+                translated.push(c);
+            }
+            '(' | '|' => {
+                translated.push(c);
+                at_start = true;
+            }
+            '*' if was_start => translated.push_str("\\*"),
+            '^' if basic && !was_start => translated.push_str("\\^"),
+            '^' => {
+                translated.push('^');
+                at_start = true;
+            }
+            '$' if basic && !ends_group(&chars, i + 1) => translated.push_str("\\$"),
+            _ => translated.push(c),
+        }
+        i += 1;
+    }
+    translated
+}
+
+// Copies the bracket expression starting at `chars[start]`, escaping
+// what the regex crate would otherwise read as syntax. Returns the
+// index just past it. An unclosed bracket is copied as is, so the regex
+// crate reports it.
+fn bracket(chars: &[char], start: usize, translated: &mut String) -> usize {
+    let mut i = start + 1;
+    let mut class = String::from("[");
+    if chars.get(i) == Some(&'^') {
+        class.push('^');
+        i += 1;
+    }
+    if chars.get(i) == Some(&']') {
+        class.push_str("\\]");
+        i += 1;
+    }
+    while let Some(&c) = chars.get(i) {
+        match c {
+            ']' => {
+                translated.push_str(&class);
+                translated.push(']');
+                return i + 1;
+            }
+            '[' if matches!(chars.get(i + 1), Some(':' | '=' | '.')) => {
+                let kind = chars[i + 1];
+                let end = (i + 2..chars.len().saturating_sub(1)).find(|&j| chars[j] == kind && chars[j + 1] == ']');
+                match end {
+                    Some(end) => {
+                        class.extend(&chars[i..end + 2]);
+                        i = end + 2;
+                        continue;
+                    }
+                    None => class.push_str("\\["),
+                }
+            }
+            '\\' | '[' | '&' | '~' => {
+                class.push('\\');
+                class.push(c);
+            }
+            _ => class.push(c),
+        }
+        i += 1;
+    }
+    translated.extend(&chars[start..]);
+    chars.len()
+}
+
+// Returns true if `chars[i..]` is empty or starts by closing a group
+// or alternative, where a basic `$` is an anchor.
+fn ends_group(chars: &[char], i: usize) -> bool {
+    match chars.get(i) {
+        None => true,
+        Some('\\') => matches!(chars.get(i + 1), Some(')' | '|')),
+        Some(_) => false,
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_basic() {
+        assert_eq!(translate(r"\(ab\)\{2\}x+", true), r"(ab){2}x\+");
+        assert_eq!(translate(r"*a\|^b$\|c$d", true), r"\*a|^b$|c\$d");
+        assert_eq!(translate(r"a^b (c)", true), r"a\^b \(c\)")
+    }
+
+    #[test]
+    fn test_translate_extended() {
+        assert_eq!(translate(r"(ab){2}|*c\.", false), r"(ab){2}|\*c\.");
+        assert_eq!(translate(r"x^", false), r"x^")
+    }
+
+    #[test]
+    fn test_translate_brackets() {
+        assert_eq!(translate(r"[]a\[:alpha:]]", false), r"[\]a\\[:alpha:]]");
+        assert_eq!(translate(r"[^a&&b]", true), r"[^a\&\&b]");
+        assert_eq!(translate("[ab", false), "[ab")
+    }
+}
@@ -0,0 +1,144 @@
+//! Filtering log lines by a leading timestamp.
+//!
+//! Used for `--since` and `--until`. Each line is checked for a timestamp
+//! at its start, written in the format given by `--time-format`. Lines
+//! that don't start with a timestamp, such as the rest of a stack trace,
+//! belong to the last line that did.
+use crate::CommandArgs;
+
+
+// The timestamp format used when `--time-format` is not given.
+pub(crate) const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+// One piece of a timestamp format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Item {
+    Literal(char),
+    // A number stored at `field` of a `Timestamp`, written with `width` digits.
+    Number { field: usize, width: usize },
+}
+
+// Year, month, day, hour, minute and second. Fields missing from the
+// format are zero, so timestamps compare in time order.
+type Timestamp = [u32; 6];
+
+// The `--since` and `--until` window, along with whether the last
+// timestamp seen was inside it.
+#[derive(Clone)]
+pub(crate) struct TimeWindow {
+    format: Vec<Item>,
+    since: Option<Timestamp>,
+    until: Option<Timestamp>,
+    inside: bool,
+}
+
+impl TimeWindow {
+    // Builds the window requested by `args`.
+    // Returns `None` if neither `--since` nor `--until` was given, or an
+    // error if the format or either bound is invalid.
+    pub(crate) fn new(args: &CommandArgs) -> Result<Option<TimeWindow>, String> {
+        if args.since.is_none() && args.until.is_none() {
+            return Ok(None);
+        }
+        let format = parse_format(args.time_format.as_deref().unwrap_or(DEFAULT_TIME_FORMAT))?;
+        let bound = |time: &Option<String>| {
+            time.as_deref()
+                .map(|time| match parse_timestamp(&format, time) {
+                    Some((timestamp, "")) => Ok(timestamp),
+                    _ => Err(format!("time '{time}' does not match the time format")),
+                })
+                .transpose()
+        };
+        let since = bound(&args.since)?;
+        let until = bound(&args.until)?;
+        Ok(Some(TimeWindow { format, since, until, inside: false }))
+    }
+
+    // Returns true if `line` is inside the window.
+    // Lines are expected in order, since lines without a timestamp take
+    // the result of the line before them.
+    pub(crate) fn accepts(&mut self, line: &str) -> bool {
+        if let Some((timestamp, _)) = parse_timestamp(&self.format, line) {
+            self.inside = self.since.is_none_or(|since| timestamp >= since)
+                && self.until.is_none_or(|until| timestamp <= until);
+        }
+        self.inside
+    }
+}
+
+// Parses a format made of literal characters and the specifiers %Y, %m,
+// %d, %H, %M and %S. `%%` is a literal percent sign.
+fn parse_format(format: &str) -> Result<Vec<Item>, String> {
+    let mut items = Vec::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            items.push(Item::Literal(c));
+            continue;
+        }
+        let item = match chars.next() {
+            Some('Y') => Item::Number { field: 0, width: 4 },
+            Some('m') => Item::Number { field: 1, width: 2 },
+            Some('d') => Item::Number { field: 2, width: 2 },
+            Some('H') => Item::Number { field: 3, width: 2 },
+            Some('M') => Item::Number { field: 4, width: 2 },
+            Some('S') => Item::Number { field: 5, width: 2 },
+            Some('%') => Item::Literal('%'),
+            Some(other) => return Err(format!("unsupported time format specifier '%{other}'")),
+            None => return Err("time format ends with '%'".to_string()),
+        };
+        items.push(item);
+    }
+    Ok(items)
+}
+
+// Parses a timestamp at the start of `text`.
+// Returns the timestamp and the text after it.
+fn parse_timestamp<'t>(format: &[Item], text: &'t str) -> Option<(Timestamp, &'t str)> {
+    let mut timestamp = Timestamp::default();
+    let mut rest = text;
+    for item in format {
+        match *item {
+            Item::Literal(c) => rest = rest.strip_prefix(c)?,
+            Item::Number { field, width } => {
+                let digits = rest.get(..width).filter(|d| d.bytes().all(|b| b.is_ascii_digit()))?;
+                timestamp[field] = digits.parse().ok()?;
+                rest = &rest[width..];
+            }
+        }
+    }
+    Some((timestamp, rest))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_timestamp_leading() {
+        let format = parse_format("[%d/%m/%Y %H:%M]").unwrap();
+        let (timestamp, rest) = parse_timestamp(&format, "[05/01/2024 10:30] started").unwrap();
+
+        assert_eq!(timestamp, [2024, 1, 5, 10, 30, 0]);
+        assert_eq!(rest, " started")
+    }
+
+    #[test]
+    fn test_parse_format_unsupported() {
+        assert!(parse_format("%Y-%j").is_err())
+    }
+
+    #[test]
+    fn test_window_continuation_lines() {
+        let mut args = CommandArgs::new("".to_string(), PathBuf::new(), false, false, false, false);
+        args.since = Some("2024-01-05 10:00:00".to_string());
+        let mut window = TimeWindow::new(&args).unwrap().unwrap();
+
+        assert!(!window.accepts("2024-01-05 09:59:59 before"));
+        assert!(!window.accepts("    at main.rs:10"));
+        assert!(window.accepts("2024-01-05 10:00:00 after"));
+        assert!(window.accepts("    at main.rs:20"))
+    }
+}
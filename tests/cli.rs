@@ -0,0 +1,70 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+#[test]
+fn test_capabilities_ignores_env_opts() {
+    let output = Command::new(env!("CARGO_BIN_EXE_grepr"))
+        .arg("--capabilities")
+        .env("GREPR_OPTS", "-i --word")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("{\"version\""))
+}
+
+#[test]
+fn test_engine_default_is_literal() {
+    let path = std::env::temp_dir().join("grepr_test_engine_default.txt");
+    std::fs::write(&path, "abc\na.c\n").unwrap();
+    let search = |engine: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_grepr")).args(engine).arg("a.c").arg(&path).output().unwrap();
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    assert_eq!(search(&["--engine", "default"]), search(&[]));
+    assert!(!search(&["--engine", "default"]).contains("abc"))
+}
+
+#[test]
+fn test_completions_include_binary_flags() {
+    let output = Command::new(env!("CARGO_BIN_EXE_grepr")).args(["--generate-completions", "bash"]).output().unwrap();
+    let script = String::from_utf8(output.stdout).unwrap();
+
+    for flag in ["--examples", "--capabilities", "--check-config", "--pcre2"] {
+        assert!(script.contains(flag), "{flag} is missing")
+    }
+}
+
+#[test]
+fn test_output_modes_conflict() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/pale_blue_dot.txt");
+    for modes in [["--field", "2", "--format", "sarif"], ["--format-template", "{text}", "--replace", "x"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_grepr")).args(modes).args(["the", path]).output().unwrap();
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains("cannot be used with"))
+    }
+}
+
+#[test]
+fn test_summary_modes_conflict() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/pale_blue_dot.txt");
+    let combinations: [&[&str]; 3] = [&["--count-matches", "--format", "sarif"], &["--frequencies", "--count-per-pattern"], &["--json-input", "--format", "csv"]];
+    for modes in combinations {
+        let output = Command::new(env!("CARGO_BIN_EXE_grepr")).args(modes).args(["the", path]).output().unwrap();
+
+        assert!(!output.status.success());
+        assert!(String::from_utf8(output.stderr).unwrap().contains("cannot be used with"))
+    }
+}
+
+#[test]
+fn test_read_error_is_translated() {
+    let path = std::env::temp_dir().join("grepr_test_read_error_missing.txt");
+    let output = Command::new(env!("CARGO_BIN_EXE_grepr")).args(["--lang", "de", "the"]).arg(&path).output().unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Datei oder Verzeichnis nicht gefunden"))
+}
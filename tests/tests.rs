@@ -1,7 +1,9 @@
 use grepr::*;
+use std::ops::ControlFlow;
 use std::path::PathBuf;
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_line_case_noinvert_good() {
     let query = "this is a test.".to_string();
     let path = PathBuf::new();
@@ -23,6 +25,7 @@ fn test_search_line_case_noinvert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_line_case_noinvert_bad() {
     let query = "this is a test".to_string();
     let path = PathBuf::new();
@@ -42,6 +45,7 @@ fn test_search_line_case_noinvert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_line_nocase_noinvert_good() {
     let query = "THIS is a test.".to_string();
     let path = PathBuf::new();
@@ -63,6 +67,7 @@ fn test_search_line_nocase_noinvert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_line_nocase_noinvert_bad() {
     let query = "THIS is a test".to_string();
     let path = PathBuf::new();
@@ -82,6 +87,7 @@ fn test_search_line_nocase_noinvert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_line_nocase_invert_good() {
     let query = "THIS is a test.".to_string();
     let path = PathBuf::new();
@@ -103,6 +109,7 @@ fn test_search_line_nocase_invert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_line_nocase_invert_bad() {
     let query = "THIS is a test".to_string();
     let path = PathBuf::new();
@@ -122,6 +129,7 @@ fn test_search_line_nocase_invert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_word_case_noinvert_good() {
     let query = "another".to_string();
     let path = PathBuf::new();
@@ -143,6 +151,7 @@ fn test_search_word_case_noinvert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_word_case_noinvert_bad() {
     let query = "nothing".to_string();
     let path = PathBuf::new();
@@ -162,6 +171,7 @@ fn test_search_word_case_noinvert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_word_nocase_noinvert_good() {
     let query = "ANOTHER".to_string();
     let path = PathBuf::new();
@@ -183,6 +193,7 @@ fn test_search_word_nocase_noinvert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_word_nocase_noinvert_bad() {
     let query = "NOTHING".to_string();
     let path = PathBuf::new();
@@ -202,6 +213,7 @@ fn test_search_word_nocase_noinvert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_word_nocase_invert_good() {
     let query = "another".to_string();
     let path = PathBuf::new();
@@ -223,6 +235,7 @@ fn test_search_word_nocase_invert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_word_nocase_invert_bad() {
     let query = "nothing".to_string();
     let path = PathBuf::new();
@@ -244,6 +257,7 @@ fn test_search_word_nocase_invert_bad() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_partial_case_noinvert_good() {
     let query = "ano".to_string();
     let path = PathBuf::new();
@@ -265,6 +279,7 @@ fn test_search_partial_case_noinvert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_partial_case_noinvert_bad() {
     let query = "nothing".to_string();
     let path = PathBuf::new();
@@ -284,6 +299,7 @@ fn test_search_partial_case_noinvert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_partial_nocase_noinvert_good() {
     let query = "ANO".to_string();
     let path = PathBuf::new();
@@ -305,6 +321,7 @@ fn test_search_partial_nocase_noinvert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_partial_nocase_noinvert_bad() {
     let query = "NOTHING".to_string();
     let path = PathBuf::new();
@@ -324,6 +341,7 @@ fn test_search_partial_nocase_noinvert_bad() {
 }
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_partial_nocase_invert_good() {
     let query = "ano".to_string();
     let path = PathBuf::new();
@@ -345,6 +363,7 @@ fn test_search_partial_nocase_invert_good() {
 
 
 #[test]
+#[allow(clippy::needless_borrow)]
 fn test_search_partial_nocase_invert_bad() {
     let query = "nothing".to_string();
     let path = PathBuf::new();
@@ -362,3 +381,337 @@ fn test_search_partial_nocase_invert_bad() {
     assert_eq!(result.len(), 2)
 }
 
+
+
+#[test]
+fn test_find_with_stops_early() {
+    let query = "test".to_string();
+    let path = PathBuf::new();
+    let contents = "this is a test.\nthis is another test!";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+    let search = Search::new(contents);
+    let mut seen = Vec::new();
+    let flow = search.find_with(&args, |m| {
+        seen.push(m.line);
+        ControlFlow::Break(m.number)
+    });
+
+    assert_eq!(flow.unwrap(), ControlFlow::Break(0));
+    assert_eq!(seen, vec!["this is a test."])
+}
+
+#[test]
+fn test_find_cancelled() {
+    let query = "test".to_string();
+    let path = PathBuf::new();
+    let contents = "this is a test.\nthis is another test!";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+    let token = CancellationToken::new();
+    let mut search = Search::new(contents);
+    search.set_cancellation(token.clone());
+    token.cancel();
+    let error = search.find(&args).unwrap_err();
+
+    assert!(error.downcast_ref::<Cancelled>().is_some());
+    assert_eq!(search.get_results().len(), 0)
+}
+
+#[test]
+fn test_find_reports_progress() {
+    let query = "test".to_string();
+    let path = PathBuf::new();
+    let contents = "this is a test.\nthis is another test!";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+    let reports = std::cell::RefCell::new(Vec::new());
+    let mut search = Search::new(contents);
+    search.set_progress(|progress| reports.borrow_mut().push(*progress));
+    let _ = search.find(&args);
+    drop(search);
+    let reports = reports.into_inner();
+
+    assert_eq!(reports.last().unwrap().files_searched, 1);
+    assert_eq!(reports.last().unwrap().bytes_scanned, contents.len())
+}
+
+#[test]
+fn test_find_many_per_query_results() {
+    let path = PathBuf::new();
+    let contents = "this is a test.\nthis is another test!";
+
+    let queries = [
+        CommandArgs::new("test".to_string(), path.clone(), false, false, false, false),
+        CommandArgs::new("ANOTHER".to_string(), path.clone(), true, false, true, false),
+        CommandArgs::new("nothing".to_string(), path, false, false, false, false),
+    ];
+    let search = Search::new(contents);
+    let results = search.find_many(&queries).unwrap();
+
+    assert_eq!(results[0].len(), 2);
+    assert_eq!(results[1], vec![(1, "this is another test!")]);
+    assert_eq!(results[2].len(), 0)
+}
+
+#[test]
+fn test_find_match_columns_multibyte() {
+    let query = "CAFÉ".to_string();
+    let path = PathBuf::new();
+    let contents = "no match here\n— le café est prêt";
+    let ignore_case = true;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let m = search.get_matches()[0];
+
+    assert_eq!(&m.line[m.span.unwrap().start..m.span.unwrap().end], "café");
+    assert_eq!(m.byte_column(), Some(8));
+    assert_eq!(m.char_column(), Some(6))
+}
+
+#[test]
+fn test_search_normalize_nfkc() {
+    let query = "file".to_string();
+    let path = PathBuf::new();
+    let contents = "the \u{fb01}le\nthe f\u{131}le";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = true;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_normalize(Some(Normalization::Nfkc));
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let result = search.get_results();
+
+    assert_eq!(result, &vec![(0, "the \u{fb01}le")])
+}
+
+#[test]
+fn test_search_fuzzy_word() {
+    let query = "seperate".to_string();
+    let path = PathBuf::new();
+    let contents = "keep them separate\nkeep them apart\nkeep them desperate";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = true;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_fuzzy(Some(1));
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let result = search.get_results();
+
+    assert_eq!(result, &vec![(0, "keep them separate")])
+}
+
+#[test]
+fn test_search_any_of() {
+    let query = "timeout".to_string();
+    let path = PathBuf::new();
+    let contents = "connection timeout\nconnection refused\nconnection reset";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_any_of(vec!["reset".to_string()]);
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let result = search.get_results();
+
+    assert_eq!(result, &vec![(0, "connection timeout"), (2, "connection reset")])
+}
+
+#[test]
+fn test_search_near_words() {
+    let query = "".to_string();
+    let path = PathBuf::new();
+    let contents = "database error\nerror: could not reach the database\nerror in the database";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_near(Some("error,database:3".parse().unwrap()));
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let result = search.get_results();
+
+    assert_eq!(result, &vec![(0, "database error"), (2, "error in the database")])
+}
+
+#[test]
+fn test_search_line_range() {
+    let query = "test".to_string();
+    let path = PathBuf::new();
+    let contents = "test one\ntest two\ntest three\ntest four";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_line_range(Some("1:2".parse().unwrap()));
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let result = search.get_results();
+
+    assert_eq!(result, &vec![(1, "test two"), (2, "test three")])
+}
+
+#[test]
+fn test_count_matches_multiple_per_line() {
+    let query = "ab".to_string();
+    let path = PathBuf::new();
+    let contents = "abab and AB\nAb\nnothing here";
+    let ignore_case = true;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+    let search = Search::new(contents);
+
+    assert_eq!(search.count_matches(&args).unwrap(), 4)
+}
+
+#[test]
+#[cfg(unix)]
+fn test_read_skips_devices() {
+    let query = "test".to_string();
+    let path = PathBuf::from("/dev/null");
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+
+    assert!(args.read().is_err());
+    assert_eq!(args.with_devices(Devices::Read).read().unwrap(), "")
+}
+
+#[test]
+fn test_find_max_line_length_stats() {
+    let query = "needle".to_string();
+    let path = PathBuf::new();
+    let contents = "short needle\nvery long line with a needle at the end";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_max_line_length(Some(20), false);
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+    let stats = search.get_stats();
+
+    assert_eq!(search.get_results(), &vec![(0, "short needle")]);
+    assert_eq!(stats.lines_searched, 1);
+    assert_eq!(stats.lines_skipped, 1)
+}
+
+#[test]
+fn test_find_literal_matches_line_scan() {
+    let query = "ab".to_string();
+    let path = PathBuf::new();
+    let contents = "ab ab\r\nxx\n\nxab\r\nabab\nend ab";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query.clone(), path.clone(), ignore_case, invert_match, word, line);
+    let mut fast = Search::new(contents);
+    let _ = fast.find(&args);
+    // A line length limit rules out the whole-contents scan.
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_max_line_length(Some(usize::MAX), false);
+    let mut slow = Search::new(contents);
+    let _ = slow.find(&args);
+
+    assert_eq!(fast.get_matches(), slow.get_matches());
+    assert_eq!(fast.get_stats(), slow.get_stats());
+    assert_eq!(fast.get_results(), &vec![(0, "ab ab"), (3, "xab"), (4, "abab"), (5, "end ab")])
+}
+
+#[test]
+fn test_find_with_literal_break_stats() {
+    let query = "ab".to_string();
+    let path = PathBuf::new();
+    let contents = "xx\r\nab ab\r\nxx\nabab\nend ab";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query.clone(), path.clone(), ignore_case, invert_match, word, line);
+    let fast = Search::new(contents);
+    let first = fast.find_with(&args, |m| ControlFlow::Break(m.number)).unwrap();
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_max_line_length(Some(usize::MAX), false);
+    let slow = Search::new(contents);
+    let _ = slow.find_with(&args, |m| ControlFlow::Break(m.number)).unwrap();
+
+    assert_eq!(first, ControlFlow::Break(1));
+    assert_eq!(fast.get_stats(), slow.get_stats());
+    assert_eq!(fast.get_stats().lines_searched, 2)
+}
+
+#[test]
+fn test_line_crlf() {
+    let query = "this is a test.".to_string();
+    let path = PathBuf::new();
+    let contents = "this is a test.\r\nthis is another test!\r\n";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = true;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line);
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+
+    assert_eq!(search.get_results(), &vec![(0, "this is a test.")])
+}
+
+#[test]
+fn test_max_total() {
+    let query = "test".to_string();
+    let path = PathBuf::new();
+    let contents = "test one\nno match\ntest two\ntest three";
+    let ignore_case = false;
+    let invert_match = false;
+    let word = false;
+    let line = false;
+
+    let args = CommandArgs::new(query, path, ignore_case, invert_match, word, line)
+        .with_max_total(Some(2));
+    let mut search = Search::new(contents);
+    let _ = search.find(&args);
+
+    assert_eq!(search.get_results(), &vec![(0, "test one"), (2, "test two")])
+}